@@ -5,6 +5,7 @@ import numpy as np
 import pdb
 import math
 import board as Board
+import export_mlp
 import struct
 from tensorflow.python import debug as tf_debug
 
@@ -92,9 +93,11 @@ for epoch in range(100):
     total_cost += batch_cost
 
   print(f"Cost {(total_cost / n_batches): 12,.0f}")
+  # Trained on the values tdlearn recorded, which are points still to come.
+  export_mlp.export_session(sess, '2048nn.mlp', future_points=True)
 
   for game_num in range(10):
-    board = Board.comp_move(0)
+    board = Board.start_board()
     while True:
       board = Board.comp_move(board)
       bestboard = None
@@ -1,6 +1,11 @@
 import array
 import builtins
 
+# Ranks of the tiles placed by the computer with their relative weights.
+SPAWNS = [(1, 9), (2, 1)]
+SPAWNS_PER_TURN = 1
+START_TILES = 2
+
 _SLIDE_RIGHT_TABLE = array.array('H', range(65536))
 _SLIDE_LEFT_TABLE = array.array('H', range(65536))
 
@@ -62,15 +67,41 @@ def empty(board):
   n2 = (n1 + (n1 >> 16) + (n1 >> 32) + (n1 >> 48)) & 0x1f
   return n2
 
-def comp_move(board):
+# Sets the tiles spawned as (rank, weight) pairs, the number placed after
+# each move and the number on the board at the start of a game.
+def set_rules(spawns, spawns_per_turn=1, start_tiles=2):
+  global SPAWNS, SPAWNS_PER_TURN, START_TILES
+  SPAWNS = list(spawns)
+  SPAWNS_PER_TURN = spawns_per_turn
+  START_TILES = start_tiles
+
+def spawn_tile(board):
   n = _rng(empty(board))
   pos = -1
   while n >= 0:
     pos += 1
     if get_tile(board, pos) == 0:
       n -= 1
-  four = _rng(10) == 0
-  return set_tile(board, pos, 2 if four else 1)
+  # Walked from the end like in the Rust version, so that seeded games match.
+  r = _rng(sum(weight for _, weight in SPAWNS))
+  for rank, weight in reversed(SPAWNS):
+    if r < weight:
+      return set_tile(board, pos, rank)
+    r -= weight
+
+def comp_move(board):
+  for _ in range(SPAWNS_PER_TURN):
+    if empty(board) == 0:
+      break
+    board = spawn_tile(board)
+  return board
+
+# The board which the first comp_move completes to the start tiles.
+def start_board():
+  board = 0
+  for _ in range(START_TILES - SPAWNS_PER_TURN):
+    board = spawn_tile(board)
+  return board
 
 def slide(board, dir):
   if dir == 0:
@@ -0,0 +1,45 @@
+# Writes the slide tables of board.py, and what board.py does to a set of
+# random boards, to a file which the Rust tests compare the Rust boards
+# against. Rerun after changing the board logic on either side:
+#
+#   python3 dump_fixture.py ../rust/tests/fixtures/board_py.bin
+#
+# The file is little endian: the 65536 entries of the right and then the left
+# slide table as u16, the number of boards as u32, and then for each board
+# the board, its transpose and the boards slid right, down, left and up as
+# u64, followed by its game score and number of empty tiles as u32.
+
+import array
+import random
+import struct
+import sys
+
+import board
+
+N_BOARDS = 1000
+
+def random_board(rng):
+  # Limiting the ranks makes equal neighbours, and so merges, common.
+  max_rank = rng.randint(1, 15)
+  res = 0
+  for tile in range(16):
+    res = board.set_tile(res, tile, rng.randint(0, max_rank))
+  return res
+
+def main():
+  rng = random.Random(2048)
+  with open(sys.argv[1], 'wb') as f:
+    for table in (board._SLIDE_RIGHT_TABLE, board._SLIDE_LEFT_TABLE):
+      out = array.array('H', table)
+      if sys.byteorder != 'little':
+        out.byteswap()
+      f.write(out.tobytes())
+    f.write(struct.pack('<I', N_BOARDS))
+    for _ in range(N_BOARDS):
+      b = random_board(rng)
+      f.write(struct.pack('<6Q2I', b, board.transpose(b),
+                          *[board.slide(b, dir) for dir in range(4)],
+                          board.game_score(b), board.empty(b)))
+
+if __name__ == '__main__':
+  main()
@@ -0,0 +1,49 @@
+# Writes dense ReLU networks, such as those trained by 2048nn.py and
+# td2048.py, in the format read by the Rust mlp module, so that they can be
+# used with expmax --eval mlp.
+#
+# The file is little endian:
+#
+#   8 bytes   b'2048MLPW'
+#   u8        version, 1
+#   u8        flags; bit 0 is set if the network predicts the points still to
+#             be earned, which the search adds to the points already earned
+#   f32       what tile ranks are divided by to make the network inputs, one
+#             input per tile in the order of board_to_array
+#   u32       number of layers, L
+#   u32 * L+1 size of the input and of the output of each layer
+#   for each layer, its weights as f32 with one row per output, followed by
+#   its biases as f32
+#
+# Every layer, including the last, is followed by a ReLU.
+
+import array
+import struct
+import sys
+
+MAGIC = b'2048MLPW'
+VERSION = 1
+FUTURE_POINTS = 1
+
+def write(filename, weights, biases, future_points, input_divisor=13):
+  sizes = [len(weights[0][0])] + [len(w) for w in weights]
+  with open(filename, 'wb') as f:
+    f.write(MAGIC)
+    f.write(struct.pack('<BBfI', VERSION, FUTURE_POINTS if future_points else 0,
+                        input_divisor, len(weights)))
+    f.write(struct.pack('<%dI' % len(sizes), *sizes))
+    for w, b in zip(weights, biases):
+      out = array.array('f', [float(x) for row in w for x in row] + [float(x) for x in b])
+      if sys.byteorder != 'little':
+        out.byteswap()
+      f.write(out.tobytes())
+
+# Writes the network of a TensorFlow session whose layers are the variables
+# W1, b1, W2, b2 and so on, as set up by 2048nn.py and td2048.py.
+def export_session(sess, filename, future_points):
+  import tensorflow as tf
+  variables = dict((v.name.split(':')[0], v) for v in tf.trainable_variables())
+  n_layers = len([name for name in variables if name.startswith('W')])
+  weights = sess.run([variables['W%d' % l] for l in range(1, n_layers + 1)])
+  biases = sess.run([variables['b%d' % l] for l in range(1, n_layers + 1)])
+  write(filename, weights, [b.reshape(-1) for b in biases], future_points)
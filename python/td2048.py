@@ -3,6 +3,7 @@
 import tensorflow as tf
 import numpy as np
 import board as Board
+import export_mlp
 import pdb
 import math
 from tensorflow.python import debug as tf_debug
@@ -61,9 +62,10 @@ boards = np.zeros(m, np.uint64)
 prev_exp_vals = np.zeros((1, m))
 
 for i in range(m):
-  boards[i] = Board.comp_move(0)
+  boards[i] = Board.start_board()
   board_to_array(int(boards[i]), prev_arr, i)
 
+n_batches = 0
 while True:
   for i in range(m):
     board = Board.comp_move(int(boards[i]))
@@ -100,7 +102,7 @@ while True:
     if bestboard == None:
       #Board.print(board)
       print("Game score: " + str(Board.game_score(board)))
-      bestboard = Board.comp_move(0)
+      bestboard = Board.start_board()
       newboards.append(bestboard)
       bestcol = 0
       board_to_array(bestboard, eval_arrays[0], 0)
@@ -119,5 +121,10 @@ while True:
     alpha *= 1.05
   prev_arr, prev_arr_next = prev_arr_next, prev_arr
 
+  # The network learns the number of moves left, not points.
+  n_batches += 1
+  if n_batches % 1000 == 0:
+    export_mlp.export_session(sess, 'td2048.mlp', future_points=False)
+
 
 #sess.close()
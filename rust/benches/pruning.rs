@@ -0,0 +1,35 @@
+extern crate p2048;
+
+use std::time::Instant;
+use p2048::board::Board;
+use p2048::config::{Pruning, SearchConfig};
+use p2048::rules::Ruleset;
+use p2048::search::{SearchStats, Searcher};
+
+// Positions from the early, middle and late game.
+const BOARDS: [u64; 6] = [0x1234_0000_0100_0001, 0x0012_0123_1235_2349, 0x1002_3210_4563_789a,
+                          0x0100_2210_5431_9876, 0x1213_2342_5670_cba9, 0x0000_1102_2433_dcba];
+
+// Searches each board with a fresh table, so that no search gains from
+// another.
+fn run(name: &str, pruning: Pruning) {
+  let rules = Ruleset::default();
+  let config = SearchConfig { pruning, ..SearchConfig::default() };
+  let start = Instant::now();
+  let mut stats = SearchStats::default();
+  let mut exps = Vec::new();
+  for bits in BOARDS.iter() {
+    let mut searcher = Searcher::new(&rules, 64);
+    searcher.set_config(config).unwrap();
+    let res = searcher.search(Board(*bits));
+    stats.add(res.stats);
+    exps.push(format!("{:.0}", res.exp));
+  }
+  println!("{:<6} {:10} nodes {:5.1}% hits {:7.3} s  exp {}", name, stats.nodes, 100.0 * stats.hit_rate(),
+           start.elapsed().as_secs_f64(), exps.join(" "));
+}
+
+fn main() {
+  run("none", Pruning::None);
+  run("star1", Pruning::Star1);
+}
@@ -0,0 +1,50 @@
+extern crate p2048;
+
+use std::hint::black_box;
+use std::time::Instant;
+use p2048::board::Board;
+use p2048::direction::Direction;
+use p2048::heuristic::{Heuristic, HeuristicParams};
+
+// The boards from the sliding tests in board.rs.
+const BOARDS: [u64; 12] = [0x0001_0001_0002_0003, 0x0001_0020_0300_1000, 0x0001_0020_0300_1001,
+                           0x0001_0022_0300_1001, 0x1111_0000_0000_0000, 0x2235_0000_0000_0000,
+                           0xcbbc_0010_0600_8000, 0x5550_0333_aa0a_c0cc, 0x1110_0222_bb0b_e0ee,
+                           0x530c_50ac_53a0_03ac, 0x02be_10be_12b0_120e, 0x1234_5678_9abc_def0];
+
+const ROUNDS: u32 = 2_000_000;
+
+// Vertical slides done by sliding the rows of the transposed board, as they
+// were before the column tables.
+fn slide_transposed(board: Board, dir: Direction) -> Board {
+  match dir {
+    Direction::Down => board.transpose().slide_right().transpose(),
+    Direction::Up => board.transpose().slide_left().transpose(),
+    _ => board.slide(dir),
+  }
+}
+
+fn time<F: Fn(Board, Direction) -> Board>(name: &str, dirs: &[Direction], slide: F) {
+  let start = Instant::now();
+  let mut acc = 0u64;
+  for _ in 0..ROUNDS {
+    for bits in BOARDS.iter() {
+      for dir in dirs.iter() {
+        acc ^= slide(black_box(Board(*bits)), *dir).0;
+      }
+    }
+  }
+  black_box(acc);
+  let slides = ROUNDS as f64 * (BOARDS.len() * dirs.len()) as f64;
+  println!("{:<24} {:6.2} ns/slide", name, start.elapsed().as_secs_f64() * 1e9 / slides);
+}
+
+fn main() {
+  let vertical = [Direction::Down, Direction::Up];
+  time("vertical, columns", &vertical, |board, dir| board.slide(dir));
+  time("vertical, transposed", &vertical, slide_transposed);
+  time("all, columns", &Direction::ALL, |board, dir| board.slide(dir));
+  time("all, transposed", &Direction::ALL, slide_transposed);
+  let heuristic = Heuristic::new(HeuristicParams::default());
+  time("heuristic", &[Direction::Right], |board, _| Board(heuristic.score(board) as u64));
+}
@@ -2,35 +2,263 @@
 
 extern crate std;
 
+use direction::{Direction, DirSet};
+use heuristic;
+use notation;
+use rng::Rng;
+use rules::Ruleset;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Board(pub u64);
 
-static mut PRINTED_LINES : usize = 0;
+// Operations shared by the different board representations, so that game
+// play and search only have to be written once. Tiles are numbered row by
+// row starting in the bottom right corner.
+pub trait GameBoard: Copy + Eq + std::hash::Hash + std::fmt::Debug + std::fmt::Display +
+                     std::str::FromStr<Err = String> + Send + Sync + 'static {
+  // Number of tiles along each side of the board.
+  const WIDTH: i32;
+  // Number of bits used to store each tile.
+  const TILE_BITS: u32;
+  // Highest rank a tile can reach.
+  const MAX_RANK: i32;
+
+  fn from_bits(bits: u128) -> Self;
+  fn bits(self) -> u128;
+
+  fn get_tile(self, tile: i32) -> i32;
+  fn set_tile(self, tile: i32, val: i32) -> Self;
+
+  fn slide(self, dir: Direction) -> Self;
+  fn slide_with_reward(self, dir: Direction) -> (Self, u32);
+
+  fn tiles() -> i32 {
+    Self::WIDTH * Self::WIDTH
+  }
 
-impl Board {
-  pub fn print(self, fours: i32, backtrack: bool, extra: &str) {
-    let nums = ["   ", "  2", "  4", "  8", " 16", " 32", " 64", "128", "256", "512", " 1K", " 2K", " 4K", " 8K", "16K", "32K"];
-    if backtrack && unsafe { PRINTED_LINES != 0 } {
-      print!("\x1b[{}A", unsafe { PRINTED_LINES });
-    }
-    print!("+---+---+---+---+\n");
-    for n in 0..16 {
-      print!("|{}", nums[self.get_tile(15-n) as usize]);
-      if (n % 4) == 3 {
-        print!("|\n+---+---+---+---+\n");
+  // Moves every tile to the position given by `pos(row, col)`.
+  fn remap<F: Fn(i32, i32) -> i32>(self, pos: F) -> Self {
+    let mut res = Self::from_bits(0);
+    for tile in 0..Self::tiles() {
+      let val = self.get_tile(tile);
+      if val != 0 {
+        res = res.set_tile(pos(tile / Self::WIDTH, tile % Self::WIDTH), val);
       }
     }
-    println!("Score: {}  ", self.game_score(fours));
-    let mut lines = 10;
-    if extra.len() != 0 {
-      print!("{}", extra);
-      if backtrack {
-        lines += extra.lines().count();
+    res
+  }
+
+  fn transpose(self) -> Self {
+    self.remap(|row, col| col * Self::WIDTH + row)
+  }
+
+  fn flip_horiz(self) -> Self {
+    self.remap(|row, col| (Self::WIDTH - 1 - row) * Self::WIDTH + col)
+  }
+
+  fn flip_vert(self) -> Self {
+    self.remap(|row, col| row * Self::WIDTH + Self::WIDTH - 1 - col)
+  }
+
+  fn heur_score(self) -> f32 {
+    heuristic::default_heuristic().score(self)
+  }
+
+  fn empty(self) -> i32 {
+    (0..Self::tiles()).filter(|tile| self.get_tile(*tile) == 0).count() as i32
+  }
+
+  // Bit n is set if tile n is empty.
+  fn empty_mask(self) -> u32 {
+    (0..Self::tiles()).filter(|tile| self.get_tile(*tile) == 0).fold(0, |mask, tile| mask | 1 << tile)
+  }
+
+  fn distinct(self) -> u8 {
+    let mut bits = 0u64;
+    for tile in 0..Self::tiles() {
+      bits |= 1 << self.get_tile(tile);
+    }
+    // Don't count empty tiles.
+    (bits & !1u64).count_ones() as u8
+  }
+
+  fn max_val(self) -> i32 {
+    (0..Self::tiles()).map(|tile| self.get_tile(tile)).max().unwrap()
+  }
+
+  // Points earned by the merges needed to reach the board, counting every
+  // tile above 2 as merged, even though some of the 4s were spawned.
+  fn earned_points(self) -> u64 {
+    (0..Self::tiles()).map(|tile| self.get_tile(tile)).filter(|rank| *rank >= 2)
+      .map(|rank| (rank as u64 - 1) << rank).sum()
+  }
+
+  // Places a single tile picked according to `rules` on a random empty tile,
+  // with the probabilities spawn_outcomes gives. It doesn't sample from
+  // spawn_outcomes, so that it draws the same numbers from `rng` as games
+  // did before there were rulesets, and seeded games and replays still play
+  // out the same way.
+  fn spawn_tile<R: Rng>(&mut self, rules: &Ruleset, rng: &mut R) {
+    let mut mask = self.empty_mask();
+    debug_assert!(mask != 0);
+    // Clear the empty tiles before the chosen one.
+    for _ in 0..rng.gen_range(mask.count_ones()) {
+      mask &= mask - 1;
+    }
+    *self = self.set_tile(mask.trailing_zeros() as i32, rules.spawn_rank(rng));
+  }
+
+  // Every board a single spawn can lead to under `rules`, together with the
+  // probability of it.
+  fn spawn_outcomes(self, rules: &Ruleset) -> SpawnOutcomes<'_, Self> {
+    self.spawn_outcomes_in(rules, self.empty_mask())
+  }
+
+  // Like spawn_outcomes, but spawning only on the empty tiles in `cells`,
+  // as if they were the only ones.
+  fn spawn_outcomes_in(self, rules: &Ruleset, cells: u32) -> SpawnOutcomes<'_, Self> {
+    debug_assert!(cells & !self.empty_mask() == 0);
+    SpawnOutcomes {
+      board: self,
+      empty: cells,
+      tile_prob: 1.0 / cells.count_ones() as f32,
+      spawns: rules.spawn_probs(),
+      next_spawn: 0,
+    }
+  }
+
+  // Places the tiles following a move, stopping early if the board fills up.
+  fn comp_move<R: Rng>(&mut self, rules: &Ruleset, rng: &mut R) {
+    for _ in 0..rules.spawns_per_turn {
+      if self.empty() == 0 {
+        break;
       }
+      self.spawn_tile(rules, rng);
+    }
+  }
+
+  fn new_game<R: Rng>(rules: &Ruleset, rng: &mut R) -> Self {
+    let mut board = Self::from_bits(0);
+    for _ in 0..::std::cmp::min(rules.start_tiles, Self::tiles() as u32) {
+      board.spawn_tile(rules, rng);
     }
-    unsafe { PRINTED_LINES = if backtrack { lines } else { 0 } };
+    board
+  }
+
+  // The directions which change the board, together with the resulting
+  // boards.
+  fn moves(self) -> Moves<Self> {
+    Moves { board: self, next: 0 }
   }
 
+  fn legal_moves(self) -> DirSet {
+    let mut res = DirSet::default();
+    for (dir, _) in self.moves() {
+      res.insert(dir);
+    }
+    res
+  }
+
+  fn is_game_over(self) -> bool {
+    self.empty() == 0 && self.moves().next().is_none()
+  }
+
+  fn symmetries(self) -> BoardSymIter<Self> {
+    BoardSymIter { op: 0, board: self }
+  }
+
+  // The symmetry of the board with the lowest bits, together with the
+  // transform which turns the board into it.
+  fn canonical(self) -> (Self, Transform) {
+    let mut best = (self, Transform::IDENTITY);
+    for &transpose in [false, true].iter() {
+      let board = if transpose { self.transpose() } else { self };
+      let flipped = board.flip_horiz();
+      let candidates = [(board, false, false), (flipped, true, false),
+                        (board.flip_vert(), false, true), (flipped.flip_vert(), true, true)];
+      for &(candidate, flip_horiz, flip_vert) in candidates.iter() {
+        if candidate.bits() < best.0.bits() {
+          best = (candidate, Transform { transpose, flip_horiz, flip_vert });
+        }
+      }
+    }
+    best
+  }
+}
+
+impl std::fmt::Display for Board {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    notation::format(*self, f)
+  }
+}
+
+impl std::str::FromStr for Board {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Board, String> {
+    notation::parse(s)
+  }
+}
+
+impl GameBoard for Board {
+  const WIDTH: i32 = 4;
+  const TILE_BITS: u32 = 4;
+  const MAX_RANK: i32 = 15;
+
+  fn from_bits(bits: u128) -> Board {
+    Board(bits as u64)
+  }
+
+  fn bits(self) -> u128 {
+    self.0 as u128
+  }
+
+  fn get_tile(self, tile: i32) -> i32 {
+    Board::get_tile(self, tile)
+  }
+
+  fn set_tile(self, tile: i32, val: i32) -> Board {
+    Board::set_tile(self, tile, val)
+  }
+
+  fn slide(self, dir: Direction) -> Board {
+    Board::slide(self, dir)
+  }
+
+  fn slide_with_reward(self, dir: Direction) -> (Board, u32) {
+    Board::slide_with_reward(self, dir)
+  }
+
+  fn transpose(self) -> Board {
+    Board::transpose(self)
+  }
+
+  fn flip_horiz(self) -> Board {
+    Board::flip_horiz(self)
+  }
+
+  fn flip_vert(self) -> Board {
+    Board::flip_vert(self)
+  }
+
+  fn empty(self) -> i32 {
+    Board::empty(self)
+  }
+
+  fn empty_mask(self) -> u32 {
+    Board::empty_mask(self)
+  }
+
+  fn distinct(self) -> u8 {
+    Board::distinct(self)
+  }
+
+  fn max_val(self) -> i32 {
+    Board::max_val(self)
+  }
+}
+
+impl Board {
   pub fn empty(self) -> i32 {
     fn empty_debug(board: Board) -> i32 {
       let mut empty = 0;
@@ -49,6 +277,17 @@ impl Board {
     n2 as i32
   }
 
+  pub fn empty_mask(self) -> u32 {
+    // One bit for each empty tile at the bottom of its 4 bits, which are
+    // then packed together.
+    let mut b = !(self.0 | (self.0 >> 1) | (self.0 >> 2) | (self.0 >> 3)) & 0x1111_1111_1111_1111u64;
+    b = (b | (b >> 3)) & 0x0303_0303_0303_0303;
+    b = (b | (b >> 6)) & 0x000f_000f_000f_000f;
+    b = (b | (b >> 12)) & 0x0000_00ff_0000_00ff;
+    b = (b | (b >> 24)) & 0xffff;
+    b as u32
+  }
+
   pub fn distinct(self) -> u8 {
     let mut bits = 0usize;
     let mut b = self.0;
@@ -77,7 +316,7 @@ impl Board {
   }
 
   pub fn get_tile(self, tile: i32) -> i32 {
-    debug_assert!(tile >= 0 && tile < 16);
+    debug_assert!((0..16).contains(&tile));
     ((self.0 >> (tile * 4)) & 0xf) as i32
   }
 
@@ -86,80 +325,89 @@ impl Board {
     Board(self.0 | (val as u64) << (tile * 4))
   }
 
-  pub fn comp_move(&mut self) -> i32 {
-    debug_assert!(self.empty() > 0);
-    let size = self.empty();
-    let mut n = rng(size);
-    debug_assert!(self.0 == 0 || n < self.empty());
-    let mut pos = -1;
-    while n >= 0 {
-      pos += 1;
-      if self.get_tile(pos) == 0 {
-        n -= 1;
-      }
+  pub fn slide(self, dir: Direction) -> Board {
+    match dir {
+      Direction::Right => self.slide_right(),
+      Direction::Down => self.slide_down(),
+      Direction::Left => self.slide_left(),
+      Direction::Up => self.slide_up(),
     }
-    let four = rng(10) == 0;
-    self.0 = self.set_tile(pos, if four { 2 } else { 1 }).0;
-    if four { 1 } else { 0 }
   }
 
-  pub fn slide(self, dir: i32) -> Board {
+  // Like slide(), but also returns the number of points the merges are worth.
+  pub fn slide_with_reward(self, dir: Direction) -> (Board, u32) {
+    fn rows_reward(rows: [u16; 4], table: &[u32; 65536]) -> u32 {
+      table[rows[0] as usize] +
+      table[rows[1] as usize] +
+      table[rows[2] as usize] +
+      table[rows[3] as usize]
+    }
+
     match dir {
-      0 => self.slide_right(),
-      1 => self.slide_down(),
-      2 => self.slide_left(),
-      3 => self.slide_up(),
-      _ => panic!("unknown direction"),
+      Direction::Right => (self.slide_right(), rows_reward(self.rows(), &SLIDE_RIGHT_REWARD)),
+      Direction::Down => {
+        let cols = self.columns();
+        (Board::slide_columns(cols, &SLIDE_DOWN_TABLE), rows_reward(cols, &SLIDE_RIGHT_REWARD))
+      }
+      Direction::Left => (self.slide_left(), rows_reward(self.rows(), &SLIDE_LEFT_REWARD)),
+      Direction::Up => {
+        let cols = self.columns();
+        (Board::slide_columns(cols, &SLIDE_UP_TABLE), rows_reward(cols, &SLIDE_LEFT_REWARD))
+      }
     }
   }
 
+  pub fn rows(self) -> [u16; 4] {
+    [self.0 as u16, (self.0 >> 16) as u16, (self.0 >> 32) as u16, (self.0 >> 48) as u16]
+  }
+
+  // The columns laid out like rows, with the bottom tile in the low nibble.
+  // Each is gathered from the board itself rather than from its transpose,
+  // by a multiply which moves the tile of row n up to bit 48 + 4n. None of
+  // the other products of the multiply overlap, so there are no carries.
+  pub fn columns(self) -> [u16; 4] {
+    let column = |col: u32| {
+      let tiles = (self.0 >> (col * 4)) & 0x000f_000f_000f_000f;
+      (tiles.wrapping_mul(0x0001_0010_0100_1000) >> 48) as u16
+    };
+    [column(0), column(1), column(2), column(3)]
+  }
+
+  // Puts together the board of columns slid with SLIDE_DOWN_TABLE or
+  // SLIDE_UP_TABLE.
+  fn slide_columns(cols: [u16; 4], table: &[u64; 65536]) -> Board {
+    Board(
+      table[cols[0] as usize] |
+      table[cols[1] as usize] << 4 |
+      table[cols[2] as usize] << 8 |
+      table[cols[3] as usize] << 12
+      )
+  }
+
   pub fn slide_down(self) -> Board {
-    let t = self.transpose();
-    Board(unsafe {
-      (*SLIDE_RIGHT_TABLE.get_unchecked(((t.0 >> 0) & 0xffff) as usize) as u64) << 0 |
-      (*SLIDE_RIGHT_TABLE.get_unchecked(((t.0 >> 16) & 0xffff) as usize) as u64) << 16 |
-      (*SLIDE_RIGHT_TABLE.get_unchecked(((t.0 >> 32) & 0xffff) as usize) as u64) << 32 |
-      (*SLIDE_RIGHT_TABLE.get_unchecked(((t.0 >> 48) & 0xffff) as usize) as u64) << 48
-      }).transpose()
+    Board::slide_columns(self.columns(), &SLIDE_DOWN_TABLE)
   }
 
   pub fn slide_up(self) -> Board {
-    let t = self.transpose();
-    Board(unsafe {
-      (*SLIDE_LEFT_TABLE.get_unchecked(((t.0 >> 0) & 0xffff) as usize) as u64) << 0 |
-      (*SLIDE_LEFT_TABLE.get_unchecked(((t.0 >> 16) & 0xffff) as usize) as u64) << 16 |
-      (*SLIDE_LEFT_TABLE.get_unchecked(((t.0 >> 32) & 0xffff) as usize) as u64) << 32 |
-      (*SLIDE_LEFT_TABLE.get_unchecked(((t.0 >> 48) & 0xffff) as usize) as u64) << 48
-      }).transpose()
+    Board::slide_columns(self.columns(), &SLIDE_UP_TABLE)
   }
 
   pub fn slide_right(self) -> Board {
-    Board(unsafe {
-      (*SLIDE_RIGHT_TABLE.get_unchecked(((self.0 >> 0) & 0xffff) as usize) as u64) << 0 |
-      (*SLIDE_RIGHT_TABLE.get_unchecked(((self.0 >> 16) & 0xffff) as usize) as u64) << 16 |
-      (*SLIDE_RIGHT_TABLE.get_unchecked(((self.0 >> 32) & 0xffff) as usize) as u64) << 32 |
-      (*SLIDE_RIGHT_TABLE.get_unchecked(((self.0 >> 48) & 0xffff) as usize) as u64) << 48
-      })
+    Board(
+      (SLIDE_RIGHT_TABLE[((self.0 >> 0) & 0xffff) as usize] as u64) << 0 |
+      (SLIDE_RIGHT_TABLE[((self.0 >> 16) & 0xffff) as usize] as u64) << 16 |
+      (SLIDE_RIGHT_TABLE[((self.0 >> 32) & 0xffff) as usize] as u64) << 32 |
+      (SLIDE_RIGHT_TABLE[((self.0 >> 48) & 0xffff) as usize] as u64) << 48
+      )
   }
 
   pub fn slide_left(self) -> Board {
-    Board(unsafe {
-      (*SLIDE_LEFT_TABLE.get_unchecked(((self.0 >> 0) & 0xffff) as usize) as u64) << 0 |
-      (*SLIDE_LEFT_TABLE.get_unchecked(((self.0 >> 16) & 0xffff) as usize) as u64) << 16 |
-      (*SLIDE_LEFT_TABLE.get_unchecked(((self.0 >> 32) & 0xffff) as usize) as u64) << 32 |
-      (*SLIDE_LEFT_TABLE.get_unchecked(((self.0 >> 48) & 0xffff) as usize) as u64) << 48
-      })
-  }
-
-  pub fn game_score(self, fours: i32) -> i32 {
-    let mut score: i32 = 0;
-    for pos in 0..16 {
-      let val = ((self.0 >> (pos*4)) & 0xf) as i32;
-      if val >= 2 {
-        score += (val - 1) * (1 << val);
-      }
-    }
-    score - (fours * 4)
+    Board(
+      (SLIDE_LEFT_TABLE[((self.0 >> 0) & 0xffff) as usize] as u64) << 0 |
+      (SLIDE_LEFT_TABLE[((self.0 >> 16) & 0xffff) as usize] as u64) << 16 |
+      (SLIDE_LEFT_TABLE[((self.0 >> 32) & 0xffff) as usize] as u64) << 32 |
+      (SLIDE_LEFT_TABLE[((self.0 >> 48) & 0xffff) as usize] as u64) << 48
+      )
   }
 
   pub fn transpose(self) -> Board {
@@ -188,19 +436,60 @@ impl Board {
     Board(a1 >> 12 | a2 >> 4 | a3 << 4 | a4 << 12)
   }
 
-  pub fn symmetries(self) -> BoardSymIter {
-    BoardSymIter { op: 0, board: self }
+}
+
+pub struct SpawnOutcomes<'a, B> {
+  board: B,
+  // Empty tiles not yet done.
+  empty: u32,
+  tile_prob: f32,
+  spawns: &'a [(i32, f32)],
+  next_spawn: usize,
+}
+
+impl<'a, B: GameBoard> Iterator for SpawnOutcomes<'a, B> {
+  type Item = (B, f32);
+  fn next(&mut self) -> Option<(B, f32)> {
+    if self.next_spawn == self.spawns.len() {
+      self.empty &= self.empty.wrapping_sub(1);
+      self.next_spawn = 0;
+    }
+    if self.empty == 0 {
+      return None;
+    }
+    let (rank, prob) = self.spawns[self.next_spawn];
+    self.next_spawn += 1;
+    Some((self.board.set_tile(self.empty.trailing_zeros() as i32, rank), prob * self.tile_prob))
+  }
+}
+
+pub struct Moves<B> {
+  board: B,
+  next: usize,
+}
+
+impl<B: GameBoard> Iterator for Moves<B> {
+  type Item = (Direction, B);
+  fn next(&mut self) -> Option<(Direction, B)> {
+    while let Some(dir) = Direction::from_index(self.next) {
+      self.next += 1;
+      let new_board = self.board.slide(dir);
+      if new_board != self.board {
+        return Some((dir, new_board));
+      }
+    }
+    None
   }
 }
 
-pub struct BoardSymIter {
+pub struct BoardSymIter<B> {
   op: i32,
-  board: Board,
+  board: B,
 }
 
-impl Iterator for BoardSymIter {
-  type Item = Board;
-  fn next(&mut self) -> Option<Board> {
+impl<B: GameBoard> Iterator for BoardSymIter<B> {
+  type Item = B;
+  fn next(&mut self) -> Option<B> {
     // Might be simper to simply alternate calls to flip_horiz/transpose
     if self.op % 2 == 1 {
       self.board = self.board.flip_horiz();
@@ -216,13 +505,73 @@ impl Iterator for BoardSymIter {
   }
 }
 
+// One of the 8 symmetries of a square board. The board is first transposed
+// and then flipped.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Transform {
+  pub transpose: bool,
+  pub flip_horiz: bool,
+  pub flip_vert: bool,
+}
+
+impl Transform {
+  pub const IDENTITY: Transform = Transform { transpose: false, flip_horiz: false, flip_vert: false };
+
+  pub fn all() -> [Transform; 8] {
+    let mut res = [Transform::IDENTITY; 8];
+    for (n, transform) in res.iter_mut().enumerate() {
+      *transform = Transform { transpose: n & 4 != 0, flip_horiz: n & 2 != 0, flip_vert: n & 1 != 0 };
+    }
+    res
+  }
+
+  pub fn apply<B: GameBoard>(self, board: B) -> B {
+    let mut res = if self.transpose { board.transpose() } else { board };
+    if self.flip_horiz {
+      res = res.flip_horiz();
+    }
+    if self.flip_vert {
+      res = res.flip_vert();
+    }
+    res
+  }
+
+  // The direction on the transformed board which corresponds to `dir` on
+  // the original one.
+  pub fn apply_dir(self, dir: Direction) -> Direction {
+    let mut res = dir.index();
+    if self.transpose {
+      // Right becomes down and left becomes up.
+      res ^= 1;
+    }
+    if self.flip_horiz && !res.is_multiple_of(2) {
+      res ^= 2;
+    }
+    if self.flip_vert && res.is_multiple_of(2) {
+      res ^= 2;
+    }
+    Direction::from_index(res).unwrap()
+  }
+
+  pub fn inverse(self) -> Transform {
+    // Undoing the flips before transposing is the same as doing the swapped
+    // flips after it.
+    if self.transpose {
+      Transform { transpose: true, flip_horiz: self.flip_vert, flip_vert: self.flip_horiz }
+    } else {
+      self
+    }
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
+  use rng::XorShift;
+  use sized::SizedBoard;
 
   #[test]
   fn sliding() {
-    init();
     assert_eq!(Board(0x0001_0001_0002_0003).slide_up(),
                Board(0x0002_0002_0003_0000));
     assert_eq!(Board(0x0001_0001_0002_0003).slide_down(),
@@ -249,9 +598,48 @@ mod tests {
                Board(0x0000_0000_12be_23cf));
   }
 
+  #[test]
+  fn columns() {
+    assert_eq!(Board(0x0001_0020_0300_4000).columns(), [0x1000, 0x0200, 0x0030, 0x0004]);
+    assert_eq!(Board(0x1234_0000_0000_0000).columns(), [0x4000, 0x3000, 0x2000, 0x1000]);
+
+    // The column tables give the same boards as sliding the rows of the
+    // transposed board.
+    let mut rng = XorShift::new(3);
+    for _ in 0..10000 {
+      let board = Board(((rng.next_u32() as u64) << 32) | rng.next_u32() as u64);
+      let trans = board.transpose();
+      assert_eq!(board.columns(), trans.rows());
+      assert_eq!(board.slide_down(), trans.slide_right().transpose());
+      assert_eq!(board.slide_up(), trans.slide_left().transpose());
+      assert_eq!(board.slide_with_reward(Direction::Down).1, trans.slide_with_reward(Direction::Right).1);
+    }
+  }
+
+  #[test]
+  fn rewards() {
+    assert_eq!(Board(0x1111_0000_0000_0000).slide_with_reward(Direction::Right),
+               (Board(0x0022_0000_0000_0000), 8));
+    assert_eq!(Board(0x1111_0000_0000_0000).slide_with_reward(Direction::Down),
+               (Board(0x0000_0000_0000_1111), 0));
+    assert_eq!(Board(0x2235_0000_0000_0000).slide_with_reward(Direction::Left),
+               (Board(0x3350_0000_0000_0000), 8));
+    assert_eq!(Board(0x5550_0333_aa0a_c0cc).slide_with_reward(Direction::Right),
+               (Board(0x0056_0034_00ab_00cd), 64 + 16 + 2048 + 8192));
+    assert_eq!(Board(0x0001_0022_0300_1001).slide_with_reward(Direction::Up),
+               (Board(0x1321_0002_0001_0000), 0));
+    assert_eq!(Board(0x02be_10be_12b0_120e).slide_with_reward(Direction::Down),
+               (Board(0x0000_0000_12be_23cf), 4 + 8 + 4096 + 32768));
+    assert_eq!(Board(0xff00_0000_0000_0000).slide_with_reward(Direction::Left).1,
+               0);
+    for dir in Direction::ALL.iter().cloned() {
+      assert_eq!(Board(0x530c_50ac_53a0_03ac).slide_with_reward(dir).0,
+                 Board(0x530c_50ac_53a0_03ac).slide(dir));
+    }
+  }
+
   #[test]
   fn flipping() {
-    init();
     assert_eq!(Board(0x0001_0a01_0002_0003).flip_vert(),
                Board(0x1000_10a0_2000_3000));
     assert_eq!(Board(0x1234_5678_9abc_def0).flip_vert(),
@@ -268,7 +656,6 @@ mod tests {
 
   #[test]
   fn iter() {
-    init();
     let mut ans = vec![Board(0x1234_0000_0000_0000),
                        Board(0x1000_2000_3000_4000),
                        Board(0x4321_0000_0000_0000),
@@ -284,9 +671,71 @@ mod tests {
     assert!(ans.is_empty());
   }
 
+  #[test]
+  fn spawns() {
+    let board = Board(0x1234_5678_9abc_d0e0);
+    assert_eq!(board.empty_mask(), 0b101);
+    assert_eq!(Board(0).empty_mask(), 0xffff);
+    assert_eq!(Board(0x0102_0000_3000_0004).empty_mask(), 0b1010_1111_0111_1110);
+    for b in [0x1234_5678_9abc_d0e0u64, 0x0102_0000_3000_0004].iter() {
+      assert_eq!(Board(*b).empty_mask(), SizedBoard::<4>(*b as u128).empty_mask());
+    }
+
+    let outcomes: Vec<(Board, f32)> = board.spawn_outcomes(&Ruleset::default()).collect();
+    assert_eq!(outcomes, vec![(Board(0x1234_5678_9abc_d0e1), 0.45), (Board(0x1234_5678_9abc_d0e2), 0.05),
+                              (Board(0x1234_5678_9abc_d1e0), 0.45), (Board(0x1234_5678_9abc_d2e0), 0.05)]);
+    assert_eq!(Board(0x1234_5678_9abc_def1).spawn_outcomes(&Ruleset::default()).next(), None);
+
+    // spawn_tile picks each outcome about as often as spawn_outcomes says.
+    let rules = Ruleset::new(vec![(1, 6), (2, 3), (3, 1)], 1, 2).unwrap();
+    let board = Board(0x1234_0000_5678_0000);
+    let outcomes: Vec<(Board, f32)> = board.spawn_outcomes(&rules).collect();
+    let mut counts = vec![0u32; outcomes.len()];
+    let mut rng = XorShift::new(5);
+    for _ in 0..100_000 {
+      let mut spawned = board;
+      spawned.spawn_tile(&rules, &mut rng);
+      counts[outcomes.iter().position(|(outcome, _)| *outcome == spawned).unwrap()] += 1;
+    }
+    for ((_, prob), count) in outcomes.iter().zip(counts.iter()) {
+      assert!((*count as f32 / 100_000.0 - prob).abs() < 0.005);
+    }
+  }
+
+  #[test]
+  fn legal_moves() {
+    let board = Board(0x0000_0000_0000_1234);
+    assert_eq!(board.legal_moves().iter().collect::<Vec<_>>(), vec![Direction::Up]);
+    assert_eq!(board.moves().next(), Some((Direction::Up, Board(0x1234_0000_0000_0000))));
+    assert!(!board.is_game_over());
+    assert_eq!(Board(0x0000_0000_0000_1134).legal_moves().len(), 3);
+    assert!(Board(0x1234_2341_3412_4123).is_game_over());
+    assert!(!Board(0x1234_2341_3412_4114).is_game_over());
+  }
+
+  #[test]
+  fn transforms() {
+    let boards = [Board(0x1234_0000_0000_0000), Board(0x0001_0020_0300_1001), Board(0x530c_50ac_53a0_03ac)];
+    for board in boards.iter() {
+      let (canonical, transform) = board.canonical();
+      assert_eq!(transform.apply(*board), canonical);
+      assert!(board.symmetries().all(|symm| symm.0 >= canonical.0));
+      assert!(board.symmetries().all(|symm| symm.canonical().0 == canonical));
+
+      for transform in Transform::all().iter() {
+        let transformed = transform.apply(*board);
+        assert_eq!(transform.inverse().apply(transformed), *board);
+        for dir in Direction::ALL.iter().cloned() {
+          assert_eq!(transformed.slide(transform.apply_dir(dir)), transform.apply(board.slide(dir)));
+          assert_eq!(transform.inverse().apply_dir(transform.apply_dir(dir)), dir);
+        }
+      }
+    }
+    assert_eq!(Board(0x1234_0000_0000_0000).canonical().0, Board(0x0000_0000_0000_1234));
+  }
+
   #[test]
   fn basic() {
-    init();
     assert_eq!(Board(0).empty(),
                16);
     assert_eq!(Board(0x1234_5678_9abc_def0).empty(),
@@ -318,54 +767,100 @@ mod tests {
   }
 }
 
-static mut SLIDE_RIGHT_TABLE : [u16; 65536] = [0u16; 65536];
-static mut SLIDE_LEFT_TABLE : [u16; 65536] = [0u16; 65536];
-
-pub fn init() {
-  for n in 0..65536 {
-    let vals = [(n >> 0) & 0xf,
-                (n >> 4) & 0xf,
-                (n >> 8) & 0xf,
-                (n >> 12) & 0xf];
-
-    let mut res = vals[0] as u16;
-    let mut merge_val = vals[0];
-    let mut dest_pos = if merge_val == 0 { -4 } else { 0 };
-    for pos in 1..4 {
-      let val = vals[pos as usize];
-
-      if val == 0 {
-        // do nothing
-      } else if val == merge_val {
-        if (res >> dest_pos) & 0xf != 15 {
-          res += (1 as u16) << dest_pos;
-        }
-        merge_val = 0;
-      } else {
-        dest_pos += 4;
-        merge_val = val;
-        res |= (val as u16) << dest_pos;
+static SLIDE_RIGHT_TABLE : [u16; 65536] = build_slide_table(false);
+static SLIDE_LEFT_TABLE : [u16; 65536] = build_slide_table(true);
+static SLIDE_RIGHT_REWARD : [u32; 65536] = build_reward_table(false);
+static SLIDE_LEFT_REWARD : [u32; 65536] = build_reward_table(true);
+// Indexed by a column as returned by Board::columns(), giving the slid column
+// spread out over the rightmost column of a board, from where slide_down()
+// and slide_up() shift it into place.
+static SLIDE_DOWN_TABLE : [u64; 65536] = build_column_table(false);
+static SLIDE_UP_TABLE : [u64; 65536] = build_column_table(true);
+
+const fn reverse_row(row: u16) -> u16 {
+  ((row & 0xf000) >> 12) |
+  ((row & 0x0f00) >> 4) |
+  ((row & 0x00f0) << 4) |
+  ((row & 0x000f) << 12)
+}
+
+// Slides a single row towards the low nibble. Returns the new row and the
+// points earned by the merges.
+const fn slide_row_right(n: u16) -> (u16, u32) {
+  let vals = [(n >> 0) & 0xf,
+              (n >> 4) & 0xf,
+              (n >> 8) & 0xf,
+              (n >> 12) & 0xf];
+
+  let mut res = vals[0];
+  let mut reward = 0u32;
+  let mut merge_val = vals[0];
+  let mut dest_pos = if merge_val == 0 { -4 } else { 0 };
+  let mut pos = 1;
+  while pos < 4 {
+    let val = vals[pos];
+
+    if val == 0 {
+      // do nothing
+    } else if val == merge_val {
+      if (res >> dest_pos) & 0xf != 15 {
+        res += 1 << dest_pos;
+        reward += 1 << (val + 1);
       }
+      merge_val = 0;
+    } else {
+      dest_pos += 4;
+      merge_val = val;
+      res |= val << dest_pos;
     }
+    pos += 1;
+  }
+  (res, reward)
+}
 
-    fn reverse_row(row: u16) -> u16 {
-      ((row & 0xf000) >> 12) |
-      ((row & 0x0f00) >> 4) |
-      ((row & 0x00f0) << 4) |
-      ((row & 0x000f) << 12)
+// Evaluated at compile time so that the tables are usable without any
+// initialization.
+const fn build_slide_table(left: bool) -> [u16; 65536] {
+  let mut table = [0u16; 65536];
+  let mut n = 0;
+  while n < 65536 {
+    if left {
+      table[reverse_row(n as u16) as usize] = reverse_row(slide_row_right(n as u16).0);
+    } else {
+      table[n] = slide_row_right(n as u16).0;
     }
-    unsafe { SLIDE_RIGHT_TABLE[n] = res; }
-    unsafe { SLIDE_LEFT_TABLE[reverse_row(n as u16) as usize] = reverse_row(res); }
+    n += 1;
   }
+  table
 }
 
-static mut SEED: u32 = 0x17004711;
-fn rng(max: i32) -> i32 {
-  let mut x = unsafe { SEED };
-  x ^= x << 13;
-  x ^= x >> 17;
-  x ^= x << 5;
-  unsafe { SEED = x; }
-  (x % (max as u32)) as i32
+const fn build_reward_table(left: bool) -> [u32; 65536] {
+  let mut table = [0u32; 65536];
+  let mut n = 0;
+  while n < 65536 {
+    if left {
+      table[reverse_row(n as u16) as usize] = slide_row_right(n as u16).1;
+    } else {
+      table[n] = slide_row_right(n as u16).1;
+    }
+    n += 1;
+  }
+  table
+}
+
+// Spreads the nibbles of a row out over the rightmost column of a board.
+const fn unpack_column(row: u16) -> u64 {
+  let row = row as u64;
+  (row & 0x000f) | (row & 0x00f0) << 12 | (row & 0x0f00) << 24 | (row & 0xf000) << 36
 }
 
+const fn build_column_table(up: bool) -> [u64; 65536] {
+  let rows = build_slide_table(up);
+  let mut table = [0u64; 65536];
+  let mut n = 0;
+  while n < 65536 {
+    table[n] = unpack_column(rows[n]);
+    n += 1;
+  }
+  table
+}
@@ -0,0 +1,102 @@
+use std::fmt;
+
+// The direction the tiles are slid in. The discriminants are the numbers
+// used for directions in replay files.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Direction {
+  Right = 0,
+  Down = 1,
+  Left = 2,
+  Up = 3,
+}
+
+impl Direction {
+  pub const ALL: [Direction; 4] = [Direction::Right, Direction::Down, Direction::Left, Direction::Up];
+
+  pub fn index(self) -> usize {
+    self as usize
+  }
+
+  pub fn from_index(index: usize) -> Option<Direction> {
+    Direction::ALL.get(index).cloned()
+  }
+
+  // The first letter of the direction's name, as shown in replays.
+  pub fn to_char(self) -> char {
+    "RDLU".chars().nth(self.index()).unwrap()
+  }
+
+  pub fn from_char(c: char) -> Option<Direction> {
+    "RDLU".find(c.to_ascii_uppercase()).and_then(Direction::from_index)
+  }
+
+  // The direction moved by a key in manual play, using wasd.
+  pub fn from_key(key: char) -> Option<Direction> {
+    match key {
+      'd' => Some(Direction::Right),
+      's' => Some(Direction::Down),
+      'a' => Some(Direction::Left),
+      'w' => Some(Direction::Up),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for Direction {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.to_char())
+  }
+}
+
+// A set of directions, with bit n set if the direction with index n is in
+// the set.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct DirSet(pub u8);
+
+impl DirSet {
+  pub fn contains(self, dir: Direction) -> bool {
+    self.0 & (1 << dir.index()) != 0
+  }
+
+  pub fn insert(&mut self, dir: Direction) {
+    self.0 |= 1 << dir.index();
+  }
+
+  pub fn is_empty(self) -> bool {
+    self.0 == 0
+  }
+
+  pub fn len(self) -> usize {
+    self.0.count_ones() as usize
+  }
+
+  pub fn iter(self) -> impl Iterator<Item = Direction> {
+    Direction::ALL.iter().cloned().filter(move |dir| self.contains(*dir))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn conversions() {
+    for dir in Direction::ALL.iter() {
+      assert_eq!(Direction::from_index(dir.index()), Some(*dir));
+      assert_eq!(Direction::from_char(dir.to_char()), Some(*dir));
+    }
+    assert_eq!(Direction::from_char('u'), Some(Direction::Up));
+    assert_eq!(Direction::from_char('x'), None);
+    assert_eq!(Direction::from_index(4), None);
+    assert_eq!(Direction::from_key('a'), Some(Direction::Left));
+    assert_eq!(Direction::from_key('q'), None);
+
+    let mut set = DirSet::default();
+    assert!(set.is_empty());
+    set.insert(Direction::Up);
+    set.insert(Direction::Down);
+    assert_eq!(set.len(), 2);
+    assert!(set.contains(Direction::Up) && !set.contains(Direction::Left));
+    assert_eq!(set.iter().collect::<Vec<_>>(), vec![Direction::Down, Direction::Up]);
+  }
+}
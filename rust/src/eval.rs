@@ -0,0 +1,27 @@
+use board::GameBoard;
+
+// Scores the positions at the leaves of the search. The search plays the
+// move with the highest expected score and counts lost positions as 0, so
+// scores should be positive, with better positions scoring higher.
+//
+// Evaluators are shared by the threads of a search.
+pub trait Evaluator<B: GameBoard>: Send + Sync + 'static {
+  // Set by evaluators which are faster scoring many boards at once. The
+  // search then gathers the leaves below each chance node one move from the
+  // end of the search, and scores them with a single evaluate_batch.
+  const BATCHED: bool = false;
+
+  fn evaluate(&self, board: B) -> f32;
+
+  // The highest score evaluate gives, if there is one. The search needs it
+  // to prune chance nodes.
+  fn upper_bound(&self) -> Option<f32> {
+    None
+  }
+
+  fn evaluate_batch(&self, boards: &[B], scores: &mut [f32]) {
+    for (board, score) in boards.iter().zip(scores.iter_mut()) {
+      *score = self.evaluate(*board);
+    }
+  }
+}
@@ -1,318 +1,194 @@
-mod board;
-
+extern crate p2048;
 extern crate getch;
-extern crate byteorder;
 extern crate getopts;
-extern crate futures;
-extern crate futures_cpupool;
-
-use std::time::Instant;
-use std::collections::HashMap;
-use byteorder::{NativeEndian, WriteBytesExt, ReadBytesExt};
-use std::fs::File;
-use std::io::BufWriter;
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::result::Result;
 use getopts::Options;
-use futures::Future;
-use futures_cpupool::CpuPool;
-use std::cell::RefCell;
-use board::Board;
-
-thread_local!(static HASH: RefCell<HashMap<Board, (i32, f32, f32)>> = RefCell::new(HashMap::new()));
+use p2048::board::{Board, GameBoard};
+use p2048::config::{self, SearchConfig};
+use p2048::direction::Direction;
+use p2048::eval::Evaluator;
+use p2048::game::Game;
+use p2048::mlp::Mlp;
+use p2048::ntuple::{NTuple, Tuples};
+use p2048::heuristic::{Heuristic, HeuristicParams};
+use p2048::render::{self, Frame, Renderer};
+use p2048::replay::{self, Record, ReplayWriter};
+use p2048::rng::{self, XorShift};
+use p2048::rules::{self, Ruleset};
+use p2048::search::{Budget, PlayState, SearchStats, Searcher};
+use p2048::sized::SizedBoard;
+use p2048::tune::{Evaluation, Tuner};
+use p2048::wide::WideBoard;
+
+// Starts a game, either from the board given on the command line or from a
+// new one from the rules.
+fn start_game<B: GameBoard>(start: Option<&String>, rules: &Ruleset, seed: u32) -> Game<B> {
+  let rng = XorShift::new(seed);
+  match start {
+    // Already checked by parse_options.
+    Some(board_str) => Game::from_board(board_str.parse().unwrap(), rules, rng),
+    None => Game::new(rules, rng),
+  }
+}
 
-fn ai_play(until: i32, print: bool, filename: Option<&String>) -> Result<i32, std::io::Error> {
-  let mut board = Board(0);
-  let mut fours = 0;
-  fours += board.comp_move();
-  fours += board.comp_move();
+// Plays a game, drawing each position with `renderer` if there is one, and
+// otherwise only printing the final score.
+fn ai_play<B: GameBoard, E: Evaluator<B>>(until: i32, mut renderer: Option<&mut dyn Renderer>, filename: Option<&String>,
+                                          seed: u32, rules: &Ruleset, start: Option<&String>, searcher: &mut Searcher<E>)
+                                          -> Result<(u32, SearchStats), std::io::Error> {
+  let mut game = start_game::<B>(start, rules, seed);
 
   let mut file = None;
   if let Some(fname) = filename {
-    file = Some(BufWriter::new(File::create(fname)?));
+    file = Some(ReplayWriter::create(fname)?);
   }
 
-  let pool = CpuPool::new_num_cpus();
-
-  let mut state = PlayState::ZeroProbDeath;
+  let mut stats = SearchStats::default();
 
   loop {
-    let mut bestdir = -1;
-    let mut searched_depth = 0;
-    let mut bestexp = 0f32;
-    let mut best_end_prob = 1f32;
-    let mut depth: u8;
-
-    let mut searches = 0;
-
-    while {
-      depth = match state {
-        PlayState::ZeroProbDeath => std::cmp::max(3, std::cmp::max(board.distinct(), 4) - 4),
-        PlayState::LowProbDeath => std::cmp::max(3, std::cmp::max(board.distinct(), 2) - 2),
-        PlayState::HighPropDeath => std::cmp::max(3, board.distinct()),
-        PlayState::VeryHighProbDeath => 17,
-      };
-      depth > searched_depth } {
-
-      bestdir = -1;
-      bestexp = 0.0;
-      best_end_prob = 1.0;
-
-      let res = futures::future::join_all((0..4).map(|dir| {
-        pool.spawn_fn(move || -> Result<(f32, f32), ()> {
-          let new_board = board.slide(dir);
-          if new_board == board {
-            Ok((-1.0f32, 1.0f32))
-          } else {
-            HASH.with(|hash_cell| {
-              let mut hash = hash_cell.borrow_mut();
-              hash.clear();
-              Ok(ai_comp_move(new_board, depth, &mut *hash, 1f32))
-            })
-          }
-        })
-      })).wait().unwrap();
-
-      for (dir, &(exp, end_prob)) in res.iter().enumerate() {
-        if exp > bestexp {
-          bestexp = exp;
-          bestdir = dir as i32;
-          best_end_prob = end_prob;
-        }
-      }
-
-      searched_depth = depth;
-      searches += 1;
-
-      state = PlayState::from_prob(best_end_prob);
-    }
-
-    if print {
-      board.print(fours, true,
-                  &format!("Death prob: {:.9}\nDepth: {} State: {:?}          \n", best_end_prob, depth, state));
+    let board = game.board();
+    let score = game.score();
+    let res = searcher.search(board);
+    stats.add(res.stats);
+
+    if let Some(ref mut r) = renderer {
+      r.show(&Frame::new(board, score,
+                         &format!("Death prob: {:.9}\nDepth: {} State: {:?}\nNodes: {} {:.1}% hits\nBoard: {}\n",
+                                  res.end_prob, res.depth, res.state,
+                                  res.stats.nodes, 100.0 * res.stats.hit_rate(), board)))?;
     }
 
     if let Some(ref mut f) = file.as_mut() {
-      f.write_u64::<NativeEndian>(board.0)?;
-      f.write_i32::<NativeEndian>(fours)?;
-      f.write_f32::<NativeEndian>(bestexp)?;
-      f.write_f32::<NativeEndian>(best_end_prob)?;
-      f.write_i8(bestdir as i8)?;
-      f.write_u8(searched_depth as u8)?;
-      f.write_u8(searches as u8)?;
+      f.write(&Record {
+                board,
+                score,
+                exp: res.exp,
+                end_prob: res.end_prob,
+                dir: res.dir,
+                depth: res.depth,
+                searches: res.searches as u8,
+              })?;
     }
 
-    if (until > 0 && board.max_val() >= until) ||
-       bestdir == -1 {
-      break;
-    }
+    let dir = match res.dir {
+      Some(dir) if until <= 0 || board.max_val() < until => dir,
+      _ => break,
+    };
 
-    board = board.slide(bestdir);
-    fours += board.comp_move();
+    game.play(dir);
   }
 
-  if !print {
-    println!("Score: {}", board.game_score(fours));
+  if renderer.is_none() {
+    println!("Score: {} (seed {})", game.score(), seed);
   }
 
-  Ok(board.game_score(fours))
+  Ok((game.score(), stats))
 }
 
-fn ai_comp_move(board: Board, depth: u8, hash: &mut HashMap<Board, (i32, f32, f32)>, prob: f32) -> (f32, f32) {
-  if depth <= 0 || prob < 0.0001 {
-    return (board.heur_score(), 0f32);
-  }
-
-  if let Some(entry) = hash.get(&board) {
-    let (hash_depth, score, end_prob) = *entry;
-    if hash_depth >= depth as i32 {
-      return (score, end_prob);
-    }
-  }
-
-  let empty = board.empty();
-  debug_assert!(empty != 0);
-
-  let prob1 = prob / (empty as f32) * 0.9;
-  let prob2 = prob / (empty as f32) * 0.1;
-
-  let mut score = 0f32;
-  let mut end_prob = 0f32;
-  for tile in 0..16 {
-    if board.get_tile(tile) == 0 {
-      let (move_score_1, move_end_prob_1) = ai_player_move(board.set_tile(tile, 1), depth, hash, prob1);
-      let (move_score_2, move_end_prob_2) = ai_player_move(board.set_tile(tile, 2), depth, hash, prob2);
-      score += move_score_1 * 0.9 + move_score_2 * 0.1;
-      end_prob += move_end_prob_1 * 0.9 + move_end_prob_2 * 0.1;
-    }
-  }
-
-  score /= empty as f32;
-  end_prob /= empty as f32;
-
-  hash.insert(board, (depth as i32, score, end_prob));
-
-  (score, end_prob)
-}
-
-fn ai_player_move(board: Board, depth: u8, hash: &mut HashMap<Board, (i32, f32, f32)>, prob: f32)  -> (f32, f32) {
-  let mut score = 0f32;
-  let mut end_prob = 1f32;
-
-  for dir in 0..4 {
-    let new_board = board.slide(dir);
-    if new_board == board {
-      continue;
-    }
-
-    let (move_score, move_end_prob) = ai_comp_move(new_board, depth - 1, hash, prob);
-    if move_score > score {
-      score = move_score;
-      end_prob = move_end_prob;
-    }
+// Tunes the heuristic weights, starting from `start` unless there's a
+// checkpoint to resume from.
+fn tune(output: &str, population: u32, generations: u32, start: HeuristicParams, evaluation: &Evaluation)
+        -> Result<(), String> {
+  let checkpoint = format!("{}.checkpoint", output);
+  let mut evaluation = evaluation.clone();
+  let mut tuner = if Path::new(&checkpoint).exists() {
+    let tuner = Tuner::load(&checkpoint)?;
+    println!("Resuming from generation {} of {}", tuner.generation, checkpoint);
+    tuner
+  } else {
+    Tuner::new(start, evaluation.seed)
+  };
+  // The games have to be the same as before resuming.
+  evaluation.seed = tuner.seed;
+
+  while tuner.generation < generations {
+    let now = Instant::now();
+    let candidates = tuner.candidates(population);
+    let scores = evaluation.run(&candidates)?;
+    tuner.update(&candidates, &scores);
+    tuner.save(&checkpoint)?;
+
+    let (best, best_score) = tuner.best().unwrap();
+    let text = format!("# Average score {} in {} games from seed {}\n{}", best_score, evaluation.games, evaluation.seed, best);
+    fs::write(output, text).map_err(|e| format!("Can't write {}: {}", output, e))?;
+    println!("Generation {}: mean weights {:.1}, best {:.1}, best so far {:.1}, time: {:.1}",
+             tuner.generation, scores[0], scores.iter().cloned().fold(0.0, f64::max), best_score,
+             now.elapsed().as_secs_f64());
   }
-
-  (score, end_prob)
+  Ok(())
 }
 
-impl Board {
-  fn heur_score(self) -> f32 {
-    let trans = self.transpose();
-    unsafe {
-      SCORE_TABLE.get_unchecked(((self.0 >> 0) & 0xffff) as usize) +
-      SCORE_TABLE.get_unchecked(((self.0 >> 16) & 0xffff) as usize) +
-      SCORE_TABLE.get_unchecked(((self.0 >> 32) & 0xffff) as usize) +
-      SCORE_TABLE.get_unchecked(((self.0 >> 48) & 0xffff) as usize) +
-      SCORE_TABLE.get_unchecked(((trans.0 >> 0) & 0xffff) as usize) +
-      SCORE_TABLE.get_unchecked(((trans.0 >> 16) & 0xffff) as usize) +
-      SCORE_TABLE.get_unchecked(((trans.0 >> 32) & 0xffff) as usize) +
-      SCORE_TABLE.get_unchecked(((trans.0 >> 48) & 0xffff) as usize)
+// Plays the games of the AI command with the evaluator picked by `eval`,
+// and returns the total score and search statistics.
+fn play_games<B: Tuples>(games: &Games, renderer: &mut dyn Renderer, eval: &EvalType)
+                         -> Result<(u64, SearchStats), std::io::Error> {
+  match eval {
+    EvalType::Heuristic(params) => play_games_with::<B, _>(games, renderer, Arc::new(Heuristic::new(*params))),
+    EvalType::NTuple(filename) => play_games_with::<B, _>(games, renderer, Arc::new(NTuple::<B>::load(filename)?)),
+    EvalType::Mlp(filename) => {
+      let mlp = Mlp::load(filename)?;
+      if mlp.inputs() != B::tiles() as usize {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData,
+                                       format!("{}: network is for boards of {} tiles", filename, mlp.inputs())));
+      }
+      play_games_with::<B, _>(games, renderer, Arc::new(mlp))
     }
   }
 }
 
-#[derive(Debug)]
-enum PlayState {
-  ZeroProbDeath,
-  LowProbDeath,
-  HighPropDeath,
-  VeryHighProbDeath,
-}
-
-impl PlayState {
-  fn from_prob(prob: f32) -> PlayState {
-    if prob > 0.05 {
-      PlayState::VeryHighProbDeath
-    } else if prob > 0.001 {
-      PlayState::HighPropDeath
-    } else if prob > 0.0 {
-      PlayState::LowProbDeath
-    } else {
-      PlayState::ZeroProbDeath
-    }
+fn play_games_with<B: GameBoard, E: Evaluator<B>>(games: &Games, renderer: &mut dyn Renderer, evaluator: Arc<E>)
+                                                  -> Result<(u64, SearchStats), std::io::Error> {
+  let mut tot_score = 0u64;
+  let mut tot_stats = SearchStats::default();
+  let mut searcher = Searcher::with_evaluator(&games.rules, games.hash_mb, evaluator);
+  searcher.set_budget(games.budget);
+  searcher.set_config(games.search_config).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+  for n in 0..games.number {
+    let game_seed = games.seed.wrapping_add(n as u32);
+    let game_renderer: Option<&mut dyn Renderer> = if games.number == 1 { Some(&mut *renderer) } else { None };
+    searcher.new_game();
+    let (score, stats) = ai_play::<B, E>(games.until, game_renderer, games.file.as_ref(), game_seed, &games.rules,
+                                         games.board.as_ref(), &mut searcher)?;
+    tot_score += score as u64;
+    tot_stats.add(stats);
   }
+  Ok((tot_score, tot_stats))
 }
 
-const SCORE_LOST_PENALTY : f32 = 200000.0f32;
-const SCORE_MONOTONICITY_POWER : f32 = 4.0f32;
-const SCORE_MONOTONICITY_WEIGHT : f32 = 47.0f32;
-const SCORE_SUM_POWER : f32 = 3.5f32;
-const SCORE_SUM_WEIGHT : f32 = 11.0f32;
-const SCORE_MERGES_WEIGHT : f32 = 700.0f32;
-const SCORE_EMPTY_WEIGHT : f32 = 270.0f32;
-
-static mut SCORE_TABLE : [f32; 65536] = [0f32; 65536];
-
-fn init_score_table() {
-  for n in 0..65536 {
-    let vals = [(n >> 0) & 0xf,
-                (n >> 4) & 0xf,
-                (n >> 8) & 0xf,
-                (n >> 12) & 0xf];
-
-    let mut sum = 0f32;
-    let mut empty = 0;
-    let mut merges = 0;
-    let mut counter = 0;
-    let mut prev = 0;
-    for rank in vals.iter() {
-      sum += (*rank as f32).powf(SCORE_SUM_POWER);
-      if *rank == 0 {
-        empty += 1;
-      } else {
-        if prev == *rank {
-          counter += 1;
-        } else if counter > 0 {
-          merges += 1 + counter;
-          counter = 0;
-        }
-        prev = *rank;
-      }
-    }
-    if counter > 0 {
-      merges += 1 + counter;
+fn replay(filename: &str, renderer: &mut dyn Renderer) -> Result<(), std::io::Error> {
+  match replay::board_type(filename)? {
+    (4, 4) => show_replay::<Board>(filename, renderer),
+    (4, 5) => show_replay::<WideBoard>(filename, renderer),
+    (3, 4) => show_replay::<SizedBoard<3>>(filename, renderer),
+    (5, 4) => show_replay::<SizedBoard<5>>(filename, renderer),
+    (width, tile_bits) => {
+      println!("Unsupported board: {0}x{0} with {1} bit tiles", width, tile_bits);
+      Ok(())
     }
-
-    let mut monotonicity_left = 0f32;
-    let mut monotonicity_right = 0f32;
-    for j in 1..4 {
-      let i = j as usize;
-      if vals[i-1] > vals[i] {
-        monotonicity_left += (vals[i-1] as f32).powf(SCORE_MONOTONICITY_POWER) - (vals[i] as f32).powf(SCORE_MONOTONICITY_POWER);
-      } else {
-        monotonicity_right += (vals[i] as f32).powf(SCORE_MONOTONICITY_POWER) - (vals[i-1] as f32).powf(SCORE_MONOTONICITY_POWER);
-      }
-    }
-
-    let score = SCORE_LOST_PENALTY +
-                SCORE_EMPTY_WEIGHT * (empty as f32) +
-                SCORE_MERGES_WEIGHT * (merges as f32) -
-                SCORE_MONOTONICITY_WEIGHT * if monotonicity_left < monotonicity_right { monotonicity_left } else { monotonicity_right } -
-                SCORE_SUM_WEIGHT * sum;
-    unsafe { SCORE_TABLE[n] = score; }
   }
-
-  board::init();
 }
 
-fn replay(filename: &str) -> Result<(), std::io::Error> {
-
-  struct GameState {
-    board: Board,
-    fours: i32,
-    bestexp: f32,
-    best_end_prob: f32,
-    bestdir: i8,
-    depth: u8,
-    searches: u8,
-  }
-
-  let mut states: Vec<GameState>;
+fn show_replay<B: GameBoard>(filename: &str, renderer: &mut dyn Renderer) -> Result<(), std::io::Error> {
+  let states = replay::read::<B>(filename)?;
 
   {
-    let mut extra_searches = 0;
+    let mut extra_searches = 0u64;
     let mut death_sum = 0f32;
     let mut life_prob = 1f64;
-    let mut f = File::open(filename)?;
-    let n = f.metadata()?.len() / 23;
-    states = Vec::with_capacity(n as usize);
-    for _ in 0..n {
-      states.push(GameState {
-                    board: Board(f.read_u64::<NativeEndian>()?),
-                    fours: f.read_i32::<NativeEndian>()?,
-                    bestexp: f.read_f32::<NativeEndian>()?,
-                    best_end_prob: f.read_f32::<NativeEndian>()?,
-                    bestdir: f.read_i8()?,
-                    depth: f.read_u8()?,
-                    searches: f.read_u8()?,
-                  });
-      extra_searches += states.last().unwrap().searches - 1;
-      if states.last().unwrap().best_end_prob != 1.0 {
-        death_sum += states.last().unwrap().best_end_prob;
-        life_prob *= 1.0 - (states.last().unwrap().best_end_prob as f64);
+    for state in states.iter() {
+      extra_searches += state.searches as u64 - 1;
+      if state.end_prob != 1.0 {
+        death_sum += state.end_prob;
+        life_prob *= 1.0 - (state.end_prob as f64);
       }
     }
 
-    println!("Total moves: {}", n);
+    println!("Total moves: {}", states.len());
     println!("Redone searches: {}", extra_searches);
     println!("Death probability sum: {}", death_sum);
     println!("Death probability: {}", 1.0 - life_prob);
@@ -325,25 +201,25 @@ fn replay(filename: &str) -> Result<(), std::io::Error> {
   loop {
     let state = &states[pos as usize];
 
-    state.board.print(state.fours, true,
-                      &format!("Move: {}      \n\
-                                Expected heuristic score: {:.2}      \n\
-                                Probability of death: {:.9} ({:?})       \n\
-                                Searched depth: {}  \n\
-                                Number of searches: {}  \n\
-                                {}\n",
-                                pos,
-                                state.bestexp,
-                                state.best_end_prob, PlayState::from_prob(state.best_end_prob),
-                                state.depth,
-                                state.searches,
-                                if state.bestdir == -1 {
-                                  format!("End of game.         ")
-                                } else {
-                                  assert!(state.bestdir <= 3);
-                                  format!("Decided direction: {}", "RDLU".chars().nth(state.bestdir as usize).unwrap())
-                                }
-                                ));
+    renderer.show(&Frame::new(state.board, state.score,
+                              &format!("Move: {}\n\
+                                        Expected heuristic score: {:.2}\n\
+                                        Probability of death: {:.9} ({:?})\n\
+                                        Searched depth: {}\n\
+                                        Number of searches: {}\n\
+                                        Board: {}\n\
+                                        {}\n",
+                                        pos,
+                                        state.exp,
+                                        state.end_prob, PlayState::from_prob(state.end_prob, &SearchConfig::default()),
+                                        state.depth,
+                                        state.searches,
+                                        state.board,
+                                        match state.dir {
+                                          None => "End of game.".to_string(),
+                                          Some(dir) => format!("Decided direction: {}", dir),
+                                        }
+                                        )))?;
 
     pos += match io.getch()? as char {
       'q' => break,
@@ -364,44 +240,71 @@ fn replay(filename: &str) -> Result<(), std::io::Error> {
   Ok(())
 }
 
-fn play_manual() -> Result<(), std::io::Error> {
-  let mut board = Board(0);
-
-  let mut fours = 0;
-  fours += board.comp_move();
-  fours += board.comp_move();
+fn play_manual<B: GameBoard>(seed: u32, rules: &Ruleset, start: Option<&String>,
+                             renderer: &mut dyn Renderer) -> Result<(), std::io::Error> {
+  let mut game = start_game::<B>(start, rules, seed);
 
   let io = getch::Getch::new()?;
 
   loop {
-    board.print(fours, true, "");
+    let board = game.board();
+    renderer.show(&Frame::new(board, game.score(), &format!("Moves: {}\nBoard: {}\n{}", game.move_count(), board,
+                                                            if game.is_over() { "Game over.\n" } else { "" })))?;
 
-    let new_board;
-    match io.getch()? as char {
+    let key = io.getch()? as char;
+    match key {
       'q' => break,
-      'w' => new_board = board.slide_up(),
-      's' => new_board = board.slide_down(),
-      'd' => new_board = board.slide_right(),
-      'a' => new_board = board.slide_left(),
-      _ => continue,
-    }
-
-    if new_board == board {
-      continue;
+      'u' => { game.undo(); }
+      'r' => { game.redo(); }
+      _ => {
+        if let Some(dir) = Direction::from_key(key).filter(|dir| board.legal_moves().contains(*dir)) {
+          game.play(dir);
+        }
+      }
     }
-
-    board = new_board;
-    fours += board.comp_move();
   }
 
   Ok(())
 }
 
+// The games the AI plays, and how it searches them.
+struct Games {
+  file: Option<String>,
+  number: i32,
+  until: i32,
+  seed: u32,
+  rules: Ruleset,
+  board: Option<String>,
+  hash_mb: usize,
+  budget: Budget,
+  search_config: SearchConfig,
+}
+
+// How the positions at the end of the search are scored.
+enum EvalType {
+  Heuristic(HeuristicParams),
+  // Tables saved by tdlearn.
+  NTuple(String),
+  // A network exported by python/export_mlp.py.
+  Mlp(String),
+}
+
+const EVAL_NAMES: &str = "heuristic, ntuple or mlp";
+
+#[derive(Copy, Clone)]
+enum BoardType {
+  Standard,
+  Wide,
+  Size3,
+  Size5,
+}
+
 enum Command {
-  AI { file: Option<String>, number: i32, until: i32 },
+  AI { games: Games, board_type: BoardType, renderer: Box<dyn Renderer>, eval: EvalType },
   Help(String, Option<String>),
-  Manual,
-  Replay(String),
+  Manual { seed: u32, board_type: BoardType, rules: Ruleset, board: Option<String>, renderer: Box<dyn Renderer> },
+  Replay { file: String, renderer: Box<dyn Renderer> },
+  Tune { output: String, population: u32, generations: u32, start: HeuristicParams, evaluation: Evaluation },
 }
 
 fn parse_options(args: &[String]) -> Command
@@ -411,8 +314,34 @@ fn parse_options(args: &[String]) -> Command
   opts.optopt("m", "max-tile", "Maximum tile value. Stop game once a tile with a value of 2^<number> has been reached.", "number");
   opts.optopt("n", "number", "Number of games to play. Defaults to 1", "number");
   opts.optopt("f", "file", "File to save replay in. If multiple games are played, a counter is added at the end of each file name.", "FILE");
-
-  let brief = format!("Usage: {0} [options]\n       {0} replay FILE\n       {0} manual", args[0]);
+  opts.optflag("", "wide", "Store tiles in 5 bits rather than 4, allowing tiles above 32K. Slower.");
+  opts.optopt("", "size", "Width and height of the board, from 3 to 5. Defaults to 4.", "number");
+  opts.optopt("", "seed", "Seed for the tile spawns. Game number n is played with seed+n. Defaults to a time based seed.", "number");
+  opts.optopt("", "spawns", "Tiles placed by the computer with their relative weights. Defaults to 2:9,4:1.", "value:weight,...");
+  opts.optopt("", "spawns-per-turn", "Number of tiles placed after each move. Defaults to 1.", "number");
+  opts.optopt("", "start-tiles", "Number of tiles on the board at the start of a game. Defaults to 2.", "number");
+  opts.optopt("", "render", &format!("How to draw boards: {}. Defaults to redraw when writing to a terminal and plain otherwise.", render::NAMES), "NAME");
+  opts.optopt("", "hash-mb", "Megabytes of memory for the transposition table. Defaults to 256.", "number");
+  opts.optopt("", "time-per-move", "Search each move one depth deeper at a time for this many seconds, and play the move from the deepest finished search.", "seconds");
+  opts.optopt("", "nodes-per-move", "Like --time-per-move, but limiting the number of nodes searched.", "number");
+  opts.optopt("", "time-per-game", "Seconds to search for in all of a game. Once used up, moves are only searched to depth 1.", "seconds");
+  opts.optopt("", "preset", &format!("Search settings to start from: {}. Defaults to default.", config::PRESETS), "NAME");
+  opts.optopt("", "search-config", "File of \"name = value\" lines changing the search settings of the preset.", "FILE");
+  opts.optmulti("", "search-set", "Changes a single search setting, after the preset and file. Can be given more than once.", "NAME=VALUE");
+  opts.optopt("", "eval", &format!("How to score the positions at the end of the search: {}. Defaults to heuristic.", EVAL_NAMES), "NAME");
+  opts.optopt("", "ntuple", "Tables saved by tdlearn --save, for --eval ntuple.", "FILE");
+  opts.optopt("", "mlp", "Network exported by python/export_mlp.py, for --eval mlp.", "FILE");
+  opts.optopt("", "heuristic", "File of \"name = value\" lines changing the weights of the heuristic.", "FILE");
+  opts.optmulti("", "heuristic-set", "Changes a single heuristic weight, after the file. Can be given more than once.", "NAME=VALUE");
+  opts.optopt("", "population", "Candidate weight sets in each generation when tuning. Defaults to 16.", "number");
+  opts.optopt("", "generations", "Generations to tune for, counting those of a resumed run. Defaults to 20.", "number");
+  opts.optopt("", "board", "Board to start from, given as rows of tile values such as \"2 4 0 8/0 0 0 2/0 0 0 0/0 0 0 0\", or as the board's bits in hex.", "BOARD");
+
+  let brief = format!("Usage: {0} [options]\n       {0} [--render NAME] replay FILE\n       {0} [--seed number] [--size number] [--wide] [rule options] [--board BOARD] [--render NAME] manual\n       {0} [options] tune FILE\n\n\
+                       In manual play, wasd moves, u undoes a move, r redoes it and q quits.\n\n\
+                       Tuning searches for the heuristic weights scoring best in -n games per candidate, 16 unless given, using\n\
+                       the fast preset and a 16 MB hash unless given. Progress is kept in FILE.checkpoint, and a run is resumed\n\
+                       from it if it exists. The best weights are written to FILE, for use with --heuristic.", args[0]);
   let options_str = opts.usage(&brief);
 
   let matches = match opts.parse(&args[1..]) {
@@ -420,13 +349,61 @@ fn parse_options(args: &[String]) -> Command
     Err(e) => { return Command::Help(options_str, Some(format!("{}", e))); }
   };
 
+  let renderer = match matches.opt_str("render").map_or_else(|| Ok(render::default_renderer()), |name| render::from_name(&name)) {
+    Ok(renderer) => renderer,
+    Err(e) => return Command::Help(options_str, Some(e)),
+  };
+
+  let seed = matches.opt_str("seed").map_or_else(rng::seed_from_time, |seed_str|
+    seed_str.parse::<u32>().unwrap()
+  );
+  let size = matches.opt_str("size").map_or(4, |size_str|
+    size_str.parse::<i32>().unwrap()
+  );
+  let board_type = match (size, matches.opt_present("wide")) {
+    (3, false) => BoardType::Size3,
+    (4, false) => BoardType::Standard,
+    (4, true) => BoardType::Wide,
+    (5, false) => BoardType::Size5,
+    (_, true) => return Command::Help(options_str, Some("Wide tiles are only supported on 4x4 boards".to_string())),
+    _ => return Command::Help(options_str, Some(format!("Unsupported board size: {}", size))),
+  };
+
+  let default_rules = Ruleset::default();
+  let spawns = matches.opt_str("spawns").map_or(Ok(default_rules.spawns().to_vec()), |spec| rules::parse_spawns(&spec));
+  let spawns_per_turn = matches.opt_str("spawns-per-turn").map_or(default_rules.spawns_per_turn, |num_str|
+    num_str.parse::<u32>().unwrap()
+  );
+  let start_tiles = matches.opt_str("start-tiles").map_or(default_rules.start_tiles, |num_str|
+    num_str.parse::<u32>().unwrap()
+  );
+  let rules = match spawns.and_then(|spawns| Ruleset::new(spawns, spawns_per_turn, start_tiles)) {
+    Ok(rules) => rules,
+    Err(e) => return Command::Help(options_str, Some(e)),
+  };
+
+  let board = matches.opt_str("board");
+  if let Some(ref board_str) = board {
+    let parsed = match board_type {
+      BoardType::Standard => board_str.parse::<Board>().map(|_| ()),
+      BoardType::Wide => board_str.parse::<WideBoard>().map(|_| ()),
+      BoardType::Size3 => board_str.parse::<SizedBoard<3>>().map(|_| ()),
+      BoardType::Size5 => board_str.parse::<SizedBoard<5>>().map(|_| ()),
+    };
+    if let Err(e) = parsed {
+      return Command::Help(options_str, Some(e));
+    }
+  }
+
   if matches.free.first() == Some(&"replay".to_string()) &&
      matches.free.len() == 2 {
-    return Command::Replay(matches.free[1].clone());
+    return Command::Replay { file: matches.free[1].clone(), renderer };
   } else if matches.free.first() == Some(&"manual".to_string()) &&
      matches.free.len() == 1 {
-    return Command::Manual;
-  } else if !matches.free.is_empty() {
+    return Command::Manual { seed, board_type, rules, board, renderer };
+  }
+  let tuning = matches.free.first() == Some(&"tune".to_string()) && matches.free.len() == 2;
+  if !matches.free.is_empty() && !tuning {
     return Command::Help(options_str, Some(format!("Unknown argument: {}", matches.free[0])));
   }
 
@@ -440,16 +417,80 @@ fn parse_options(args: &[String]) -> Command
 
   let file = matches.opt_str("f");
 
-  let num_games = matches.opt_str("n").map_or(1, |num_str|
+  let num_games = matches.opt_str("n").map_or(if tuning { 16 } else { 1 }, |num_str|
     num_str.parse::<i32>().unwrap()
   );
 
-  Command::AI{ file, number: num_games, until: max_tile }
+  let hash_mb = matches.opt_str("hash-mb").map_or(if tuning { 16 } else { 256 }, |mb_str|
+    mb_str.parse::<usize>().unwrap()
+  );
+
+  let seconds = |name: &str| matches.opt_str(name).map(|secs_str|
+    Duration::from_secs_f64(secs_str.parse::<f64>().unwrap())
+  );
+  let budget = Budget {
+    time_per_move: seconds("time-per-move"),
+    nodes_per_move: matches.opt_str("nodes-per-move").map(|num_str| num_str.parse::<u64>().unwrap()),
+    time_per_game: seconds("time-per-game"),
+  };
+
+  let preset = matches.opt_str("preset").unwrap_or_else(|| (if tuning { "fast" } else { "default" }).to_string());
+  let search_config = SearchConfig::preset(&preset)
+    .and_then(|mut search_config| {
+      if let Some(path) = matches.opt_str("search-config") {
+        search_config.apply(&config::read_settings(&path)?)?;
+      }
+      let sets = matches.opt_strs("search-set").iter().map(|set| config::parse_settings(set)).collect::<Result<Vec<_>, _>>()?;
+      search_config.apply(&sets.concat())?;
+      search_config.validate()?;
+      Ok(search_config)
+    });
+  let search_config = match search_config {
+    Ok(search_config) => search_config,
+    Err(e) => return Command::Help(options_str, Some(e)),
+  };
+
+  let mut heuristic = HeuristicParams::default();
+  let res = matches.opt_str("heuristic").map_or(Ok(()), |path| heuristic.apply(&config::read_settings(&path)?))
+    .and_then(|_| {
+      let sets = matches.opt_strs("heuristic-set").iter().map(|set| config::parse_settings(set)).collect::<Result<Vec<_>, _>>()?;
+      heuristic.apply(&sets.concat())
+    });
+  if let Err(e) = res {
+    return Command::Help(options_str, Some(e));
+  }
+
+  if tuning {
+    if !matches!(board_type, BoardType::Standard) || board.is_some() {
+      return Command::Help(options_str, Some("Tuning plays standard games on 4x4 boards".to_string()));
+    }
+    if num_games <= 0 {
+      return Command::Help(options_str, Some("Tuning needs at least one game for each candidate".to_string()));
+    }
+    let count = |name: &str, default| matches.opt_str(name).map_or(default, |num_str| num_str.parse::<u32>().unwrap());
+    let evaluation = Evaluation { games: num_games as u32, seed, until: max_tile, rules, search_config, hash_mb };
+    return Command::Tune { output: matches.free[1].clone(), population: count("population", 16),
+                           generations: count("generations", 20), start: heuristic, evaluation };
+  }
+
+  let eval = match matches.opt_str("eval").as_deref() {
+    None | Some("heuristic") => EvalType::Heuristic(heuristic),
+    Some("ntuple") => match matches.opt_str("ntuple") {
+      Some(filename) => EvalType::NTuple(filename),
+      None => return Command::Help(options_str, Some("--eval ntuple needs --ntuple FILE".to_string())),
+    },
+    Some("mlp") => match matches.opt_str("mlp") {
+      Some(filename) => EvalType::Mlp(filename),
+      None => return Command::Help(options_str, Some("--eval mlp needs --mlp FILE".to_string())),
+    },
+    Some(name) => return Command::Help(options_str, Some(format!("Unknown evaluator: {}", name))),
+  };
+
+  let games = Games { file, number: num_games, until: max_tile, seed, rules, board, hash_mb, budget, search_config };
+  Command::AI{ games, board_type, renderer, eval }
 }
 
 fn main() {
-  init_score_table();
-
   let args: Vec<String> = std::env::args().collect();
 
   match parse_options(&args) {
@@ -459,27 +500,49 @@ fn main() {
       }
       println!("{}", options_str);
     }
-    Command::Replay(file) => {
-      replay(&file).unwrap();
+    Command::Replay { file, mut renderer } => {
+      replay(&file, renderer.as_mut()).unwrap();
     }
-    Command::Manual => {
-      play_manual().unwrap();
+    Command::Manual { seed, board_type, rules, board, mut renderer } => {
+      match board_type {
+        BoardType::Standard => play_manual::<Board>(seed, &rules, board.as_ref(), renderer.as_mut()),
+        BoardType::Wide => play_manual::<WideBoard>(seed, &rules, board.as_ref(), renderer.as_mut()),
+        BoardType::Size3 => play_manual::<SizedBoard<3>>(seed, &rules, board.as_ref(), renderer.as_mut()),
+        BoardType::Size5 => play_manual::<SizedBoard<5>>(seed, &rules, board.as_ref(), renderer.as_mut()),
+      }.unwrap();
     }
-    Command::AI{ file, number, until } => {
-      let now = Instant::now();
-      let mut tot_score = 0;
-      for _ in 0..number {
-        tot_score += ai_play(until, number == 1, file.as_ref()).unwrap();
+    Command::Tune { output, population, generations, start, evaluation } => {
+      if let Err(e) = tune(&output, population, generations, start, &evaluation) {
+        println!("{}", e);
       }
+    }
+    Command::AI{ games, board_type, mut renderer, eval } => {
+      let now = Instant::now();
+      let res = match board_type {
+        BoardType::Standard => play_games::<Board>(&games, renderer.as_mut(), &eval),
+        BoardType::Wide => play_games::<WideBoard>(&games, renderer.as_mut(), &eval),
+        BoardType::Size3 => play_games::<SizedBoard<3>>(&games, renderer.as_mut(), &eval),
+        BoardType::Size5 => play_games::<SizedBoard<5>>(&games, renderer.as_mut(), &eval),
+      };
+      let (tot_score, tot_stats) = match res {
+        Ok(totals) => totals,
+        Err(e) => {
+          println!("{}", e);
+          return;
+        }
+      };
+      let (number, seed) = (games.number, games.seed);
       let elapsed = now.elapsed();
 
       let time_sec = elapsed.as_secs() as f64 + (elapsed.subsec_nanos() as f64) / 1_000_000_000f64;
 
       if number == 1 {
+        println!("Seed: {}", seed);
         println!("Time: {}", time_sec);  
       } else {
         println!("Average score: {}, time: {}", (tot_score as f32) / (number as f32), time_sec);
       }
+      println!("Nodes: {} {:.1}% hits", tot_stats.nodes, 100.0 * tot_stats.hit_rate());
     }
   }
 }
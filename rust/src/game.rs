@@ -0,0 +1,197 @@
+use board::GameBoard;
+use direction::Direction;
+use rng::XorShift;
+use rules::Ruleset;
+
+// A move together with the tiles the computer placed after it, as (tile,
+// rank) pairs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Turn {
+  pub dir: Direction,
+  pub spawns: Vec<(i32, i32)>,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct State<B> {
+  board: B,
+  score: u32,
+  rng: XorShift,
+}
+
+// A game in progress. Every position reached is kept, so turns can be
+// undone and redone. The random generator is part of each position, which
+// means that a move which is undone and played again gets the same spawns.
+#[derive(Debug, Clone)]
+pub struct Game<B> {
+  rules: Ruleset,
+  states: Vec<State<B>>,
+  turns: Vec<Turn>,
+  // Index of the current position in `states`. Positions after it have been
+  // undone and can be redone.
+  pos: usize,
+}
+
+impl<B: GameBoard> Game<B> {
+  // Starts a new game, placing the start tiles of `rules`.
+  pub fn new(rules: &Ruleset, mut rng: XorShift) -> Game<B> {
+    let board = B::new_game(rules, &mut rng);
+    Game::with_score(board, 0, rules, rng)
+  }
+
+  // Starts from a board reached some other way, scoring it as if every tile
+  // above 2 on it had been merged.
+  pub fn from_board(board: B, rules: &Ruleset, rng: XorShift) -> Game<B> {
+    Game::with_score(board, board.earned_points() as u32, rules, rng)
+  }
+
+  fn with_score(board: B, score: u32, rules: &Ruleset, rng: XorShift) -> Game<B> {
+    Game {
+      rules: rules.clone(),
+      states: vec![State { board, score, rng }],
+      turns: Vec::new(),
+      pos: 0,
+    }
+  }
+
+  pub fn board(&self) -> B {
+    self.states[self.pos].board
+  }
+
+  pub fn score(&self) -> u32 {
+    self.states[self.pos].score
+  }
+
+  // Number of moves played to reach the current position.
+  pub fn move_count(&self) -> u32 {
+    self.pos as u32
+  }
+
+  pub fn rules(&self) -> &Ruleset {
+    &self.rules
+  }
+
+  // The generator the next spawns are drawn from. Players which need
+  // randomness of their own can draw from it too, so that a single seed
+  // reproduces the whole game.
+  pub fn rng(&mut self) -> &mut XorShift {
+    &mut self.states[self.pos].rng
+  }
+
+  // The turns played to reach the current position.
+  pub fn history(&self) -> &[Turn] {
+    &self.turns[..self.pos]
+  }
+
+  pub fn is_over(&self) -> bool {
+    self.board().is_game_over()
+  }
+
+  // Slides the tiles in `dir` and lets the computer place its tiles,
+  // returning the points scored. Any undone turns are dropped.
+  pub fn play(&mut self, dir: Direction) -> u32 {
+    debug_assert!(self.board().legal_moves().contains(dir));
+    let mut state = self.states[self.pos];
+    let (mut board, points) = state.board.slide_with_reward(dir);
+    let mut spawns = Vec::with_capacity(self.rules.spawns_per_turn as usize);
+    for _ in 0..self.rules.spawns_per_turn {
+      if board.empty() == 0 {
+        break;
+      }
+      let empty = board.empty_mask();
+      board.spawn_tile(&self.rules, &mut state.rng);
+      let tile = (empty & !board.empty_mask()).trailing_zeros() as i32;
+      spawns.push((tile, board.get_tile(tile)));
+    }
+    state.board = board;
+    state.score += points;
+
+    self.states.truncate(self.pos + 1);
+    self.turns.truncate(self.pos);
+    self.states.push(state);
+    self.turns.push(Turn { dir, spawns });
+    self.pos += 1;
+    points
+  }
+
+  // Goes back to the position before the last turn. Returns false if there
+  // is nothing to undo.
+  pub fn undo(&mut self) -> bool {
+    if self.pos == 0 {
+      return false;
+    }
+    self.pos -= 1;
+    true
+  }
+
+  // Plays the last undone turn again. Returns false if there is nothing to
+  // redo.
+  pub fn redo(&mut self) -> bool {
+    if self.pos + 1 == self.states.len() {
+      return false;
+    }
+    self.pos += 1;
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use board::Board;
+
+  #[test]
+  fn undo_redo() {
+    let rules = Ruleset::default();
+    let mut game = Game::<Board>::new(&rules, XorShift::new(5));
+
+    // Plays the same game as the board functions do by themselves.
+    let mut rng = XorShift::new(5);
+    let mut board = Board::new_game(&rules, &mut rng);
+    let mut score = 0;
+    assert_eq!(game.board(), board);
+    for _ in 0..20 {
+      let dir = board.legal_moves().iter().next().unwrap();
+      let (new_board, points) = board.slide_with_reward(dir);
+      board = new_board;
+      score += points;
+      board.comp_move(&rules, &mut rng);
+      assert_eq!(game.play(dir), points);
+      assert_eq!(game.board(), board);
+      assert_eq!(game.score(), score);
+      assert_eq!(game.history().last().unwrap().spawns.len(), 1);
+    }
+    assert_eq!(game.move_count(), 20);
+
+    let after = game.board();
+    let turn = game.history()[19].clone();
+    assert!(game.undo());
+    let before = game.board();
+    assert_eq!(game.move_count(), 19);
+    let (tile, rank) = turn.spawns[0];
+    assert_eq!(before.slide(turn.dir).set_tile(tile, rank), after);
+
+    assert!(game.redo());
+    assert_eq!(game.board(), after);
+    assert!(!game.redo());
+
+    // Playing the undone move again gets the same spawn, and playing
+    // anything drops the undone turns.
+    assert!(game.undo() && game.undo());
+    assert!(game.redo());
+    let redone = game.board();
+    assert!(game.undo());
+    game.play(game.turns[18].dir);
+    assert_eq!(game.board(), redone);
+    assert_eq!(game.move_count(), 19);
+    assert!(!game.redo());
+
+    while game.undo() {}
+    assert_eq!(game.move_count(), 0);
+    assert_eq!(game.score(), 0);
+    assert!(game.history().is_empty());
+
+    // Games started from a given board are scored from its tiles.
+    let game = Game::from_board(Board(0x0001_0020_0300_1001), &rules, XorShift::new(5));
+    assert_eq!(game.score(), 20);
+  }
+}
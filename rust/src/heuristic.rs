@@ -0,0 +1,258 @@
+use std::fmt;
+use std::sync::{LazyLock, OnceLock};
+use board::{Board, GameBoard};
+use config;
+use eval::Evaluator;
+
+// The weights of the heuristic score. Any of them can be set from a settings
+// file or the command line, and each set of weights gets its own score
+// tables, so that several can be played against each other in one process.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HeuristicParams {
+  pub lost_penalty: f32,
+  pub monotonicity_power: f32,
+  pub monotonicity_weight: f32,
+  pub sum_power: f32,
+  pub sum_weight: f32,
+  pub merges_weight: f32,
+  pub empty_weight: f32,
+}
+
+impl Default for HeuristicParams {
+  fn default() -> HeuristicParams {
+    HeuristicParams {
+      lost_penalty: 200000.0,
+      monotonicity_power: 4.0,
+      monotonicity_weight: 47.0,
+      sum_power: 3.5,
+      sum_weight: 11.0,
+      merges_weight: 700.0,
+      empty_weight: 270.0,
+    }
+  }
+}
+
+impl HeuristicParams {
+  pub fn values(&self) -> [(&'static str, f32); 7] {
+    [("lost_penalty", self.lost_penalty),
+     ("monotonicity_power", self.monotonicity_power),
+     ("monotonicity_weight", self.monotonicity_weight),
+     ("sum_power", self.sum_power),
+     ("sum_weight", self.sum_weight),
+     ("merges_weight", self.merges_weight),
+     ("empty_weight", self.empty_weight)]
+  }
+
+  // The inverse of values().
+  pub fn from_values(values: &[f32; 7]) -> HeuristicParams {
+    HeuristicParams {
+      lost_penalty: values[0],
+      monotonicity_power: values[1],
+      monotonicity_weight: values[2],
+      sum_power: values[3],
+      sum_weight: values[4],
+      merges_weight: values[5],
+      empty_weight: values[6],
+    }
+  }
+
+  pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+    let value = config::parse_value(name, value)?;
+    match name {
+      "lost_penalty" => self.lost_penalty = value,
+      "monotonicity_power" => self.monotonicity_power = value,
+      "monotonicity_weight" => self.monotonicity_weight = value,
+      "sum_power" => self.sum_power = value,
+      "sum_weight" => self.sum_weight = value,
+      "merges_weight" => self.merges_weight = value,
+      "empty_weight" => self.empty_weight = value,
+      _ => return Err(format!("Unknown heuristic weight: {}", name)),
+    }
+    Ok(())
+  }
+
+  // Applies settings such as those read by config::read_settings, or given
+  // as "name=value" on the command line.
+  pub fn apply(&mut self, settings: &[(String, String)]) -> Result<(), String> {
+    for (name, value) in settings.iter() {
+      self.set(name, value)?;
+    }
+    Ok(())
+  }
+}
+
+// Writes the weights as a settings file.
+impl fmt::Display for HeuristicParams {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for (name, value) in self.values().iter() {
+      writeln!(f, "{} = {}", name, value)?;
+    }
+    Ok(())
+  }
+}
+
+// Heuristic score of a single row or column, listed from one end to the
+// other.
+pub fn score_row(vals: &[i32], params: &HeuristicParams) -> f32 {
+  let mut sum = 0f32;
+  let mut empty = 0;
+  let mut merges = 0;
+  let mut counter = 0;
+  let mut prev = 0;
+  for rank in vals.iter() {
+    sum += (*rank as f32).powf(params.sum_power);
+    if *rank == 0 {
+      empty += 1;
+    } else {
+      if prev == *rank {
+        counter += 1;
+      } else if counter > 0 {
+        merges += 1 + counter;
+        counter = 0;
+      }
+      prev = *rank;
+    }
+  }
+  if counter > 0 {
+    merges += 1 + counter;
+  }
+
+  let mut monotonicity_left = 0f32;
+  let mut monotonicity_right = 0f32;
+  for i in 1..vals.len() {
+    if vals[i-1] > vals[i] {
+      monotonicity_left += (vals[i-1] as f32).powf(params.monotonicity_power) - (vals[i] as f32).powf(params.monotonicity_power);
+    } else {
+      monotonicity_right += (vals[i] as f32).powf(params.monotonicity_power) - (vals[i-1] as f32).powf(params.monotonicity_power);
+    }
+  }
+
+  params.lost_penalty +
+  params.empty_weight * (empty as f32) +
+  params.merges_weight * (merges as f32) -
+  params.monotonicity_weight * if monotonicity_left < monotonicity_right { monotonicity_left } else { monotonicity_right } -
+  params.sum_weight * sum
+}
+
+// The heuristic score for one set of weights. Row scores for rows of 4 bit
+// ranks are looked up rather than computed, in a table for each board width
+// which is built the first time a board of that width is scored.
+pub struct Heuristic {
+  params: HeuristicParams,
+  row_tables: [OnceLock<Vec<f32>>; 6],
+}
+
+// Built the first time a score is needed.
+static DEFAULT_HEURISTIC : LazyLock<Heuristic> = LazyLock::new(|| Heuristic::new(HeuristicParams::default()));
+
+// The heuristic with the default weights, used by GameBoard::heur_score.
+pub fn default_heuristic() -> &'static Heuristic {
+  &DEFAULT_HEURISTIC
+}
+
+impl Heuristic {
+  pub fn new(params: HeuristicParams) -> Heuristic {
+    Heuristic {
+      params,
+      row_tables: [OnceLock::new(), OnceLock::new(), OnceLock::new(),
+                   OnceLock::new(), OnceLock::new(), OnceLock::new()],
+    }
+  }
+
+  pub fn params(&self) -> &HeuristicParams {
+    &self.params
+  }
+
+  fn row_table(&self, width: i32) -> &[f32] {
+    self.row_tables[width as usize].get_or_init(|| {
+      let mut vals = vec![0; width as usize];
+      (0..1 << (4 * width)).map(|n: i32| {
+        for (pos, val) in vals.iter_mut().enumerate() {
+          *val = (n >> (4 * pos)) & 0xf;
+        }
+        score_row(&vals, &self.params)
+      }).collect()
+    })
+  }
+
+  // Heuristic score for any board type, summed over all rows and columns.
+  pub fn score<B: GameBoard>(&self, board: B) -> f32 {
+    let width = B::WIDTH;
+    let table = self.row_table(width);
+
+    if width == 4 && B::TILE_BITS == 4 {
+      let board = Board(board.bits() as u64);
+      let rows = board.rows();
+      let cols = board.columns();
+      return table[rows[0] as usize] +
+             table[rows[1] as usize] +
+             table[rows[2] as usize] +
+             table[rows[3] as usize] +
+             table[cols[0] as usize] +
+             table[cols[1] as usize] +
+             table[cols[2] as usize] +
+             table[cols[3] as usize];
+    }
+
+    let mut score = 0f32;
+    let mut buf = [0; 8];
+    let vals = &mut buf[..width as usize];
+    for b in [board, board.transpose()].iter() {
+      for row in 0..width {
+        let mut n = 0;
+        for col in 0..width {
+          vals[col as usize] = b.get_tile(row * width + col);
+          n |= vals[col as usize] << (4 * col);
+        }
+        if vals.iter().all(|rank| *rank <= 15) {
+          score += table[n as usize];
+        } else {
+          score += score_row(vals, &self.params);
+        }
+      }
+    }
+    score
+  }
+}
+
+impl<B: GameBoard> Evaluator<B> for Heuristic {
+  fn evaluate(&self, board: B) -> f32 {
+    self.score(board)
+  }
+
+  // The table holds every row unless ranks go above 15.
+  fn upper_bound(&self) -> Option<f32> {
+    if B::MAX_RANK > 15 {
+      return None;
+    }
+    let best_row = self.row_table(B::WIDTH).iter().cloned().fold(f32::MIN, f32::max);
+    Some(2.0 * B::WIDTH as f32 * best_row)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use wide::WideBoard;
+
+  #[test]
+  fn params() {
+    let mut params = HeuristicParams::default();
+    params.apply(&config::parse_settings("empty_weight = 0\nsum_power = 3").unwrap()).unwrap();
+    assert_eq!(params.empty_weight, 0.0);
+    assert_eq!(params.sum_power, 3.0);
+    assert!(params.set("empty", "1").is_err());
+    assert!(params.set("sum_weight", "x").is_err());
+    let mut read = HeuristicParams::default();
+    read.apply(&config::parse_settings(&params.to_string()).unwrap()).unwrap();
+    assert_eq!(read, params);
+
+    // Both sets of weights can be used side by side.
+    let board = Board(0x1234_0120_0013_1001);
+    let default = Heuristic::new(HeuristicParams::default());
+    let other = Heuristic::new(params);
+    assert_eq!(default.score(board), board.heur_score());
+    assert!(other.score(board) != default.score(board));
+    assert_eq!(other.score(WideBoard::from_board(board)), other.score(board));
+  }
+}
@@ -0,0 +1,25 @@
+extern crate byteorder;
+extern crate futures;
+extern crate futures_cpupool;
+
+pub mod board;
+pub mod config;
+pub mod direction;
+pub mod eval;
+pub mod game;
+pub mod heuristic;
+pub mod mlp;
+pub mod movavg;
+pub mod notation;
+pub mod reference;
+pub mod ntuple;
+pub mod render;
+pub mod replay;
+pub mod rng;
+pub mod rows;
+pub mod rules;
+pub mod search;
+pub mod sized;
+pub mod transtable;
+pub mod tune;
+pub mod wide;
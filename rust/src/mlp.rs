@@ -0,0 +1,184 @@
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use byteorder::{LittleEndian, ReadBytesExt};
+use board::GameBoard;
+use eval::Evaluator;
+
+// Files written by python/export_mlp.py, which describes the format, start
+// with this and a version byte.
+const MAGIC: &[u8; 8] = b"2048MLPW";
+const VERSION: u8 = 1;
+// Set if the network predicts the points still to be earned.
+const FUTURE_POINTS: u8 = 1;
+
+// Boards scored at once. The weights of a layer are read once for each batch
+// rather than once for each board.
+const MAX_BATCH: usize = 16;
+
+thread_local! {
+  // The activations of the layer being run and of the next one, for a batch
+  // of boards, reused by every run on the thread.
+  static SCRATCH: RefCell<(Vec<f32>, Vec<f32>)> = const { RefCell::new((Vec::new(), Vec::new())) };
+}
+
+struct Layer {
+  inputs: usize,
+  outputs: usize,
+  // One row of `inputs` weights for each output.
+  weights: Vec<f32>,
+  biases: Vec<f32>,
+}
+
+// A dense network with a ReLU after every layer, taking the ranks of the
+// tiles of a board and giving a single value.
+pub struct Mlp {
+  layers: Vec<Layer>,
+  // Size of the widest layer, inputs included.
+  widest: usize,
+  input_divisor: f32,
+  future_points: bool,
+}
+
+impl Mlp {
+  pub fn load(filename: &str) -> io::Result<Mlp> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", filename, msg));
+    let mut file = BufReader::new(File::open(filename)?);
+    let mut magic = [0u8; 8];
+    file.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+      return Err(invalid("not a network file".to_string()));
+    }
+    let version = file.read_u8()?;
+    if version != VERSION {
+      return Err(invalid(format!("unsupported network version {}", version)));
+    }
+    let flags = file.read_u8()?;
+    let input_divisor = file.read_f32::<LittleEndian>()?;
+    let n_layers = file.read_u32::<LittleEndian>()? as usize;
+    let mut sizes = vec![0u32; n_layers + 1];
+    file.read_u32_into::<LittleEndian>(&mut sizes)?;
+    if n_layers == 0 || sizes[n_layers] != 1 || sizes.contains(&0) {
+      return Err(invalid(format!("layer sizes {:?} don't end in a single output", sizes)));
+    }
+
+    let mut layers = Vec::with_capacity(n_layers);
+    for size in sizes.windows(2) {
+      let (inputs, outputs) = (size[0] as usize, size[1] as usize);
+      let mut weights = vec![0f32; inputs * outputs];
+      let mut biases = vec![0f32; outputs];
+      file.read_f32_into::<LittleEndian>(&mut weights)?;
+      file.read_f32_into::<LittleEndian>(&mut biases)?;
+      layers.push(Layer { inputs, outputs, weights, biases });
+    }
+    if file.read(&mut [0u8])? != 0 {
+      return Err(invalid("trailing data".to_string()));
+    }
+    let widest = *sizes.iter().max().unwrap() as usize;
+    Ok(Mlp { layers, widest, input_divisor, future_points: flags & FUTURE_POINTS != 0 })
+  }
+
+  // Number of inputs, which is the number of tiles of the boards the network
+  // scores.
+  pub fn inputs(&self) -> usize {
+    self.layers[0].inputs
+  }
+
+  // Runs the network on up to MAX_BATCH boards. The activations of board n
+  // start at n * widest in the scratch buffers.
+  fn run<B: GameBoard>(&self, boards: &[B], scores: &mut [f32]) {
+    debug_assert!(boards.len() <= MAX_BATCH && B::tiles() as usize == self.inputs());
+    let width = self.widest;
+    SCRATCH.with(|scratch| {
+      let (acts, next) = &mut *scratch.borrow_mut();
+      if acts.len() < MAX_BATCH * width {
+        acts.resize(MAX_BATCH * width, 0.0);
+        next.resize(MAX_BATCH * width, 0.0);
+      }
+      for (act, board) in acts.chunks_mut(width).zip(boards.iter()) {
+        for tile in 0..B::tiles() {
+          act[tile as usize] = board.get_tile(tile) as f32 / self.input_divisor;
+        }
+      }
+
+      for layer in self.layers.iter() {
+        debug_assert!(layer.inputs <= width && layer.outputs <= width);
+        for (out, (row, bias)) in layer.weights.chunks(layer.inputs).zip(layer.biases.iter()).enumerate() {
+          for n in 0..boards.len() {
+            let act = &acts[n * width..n * width + layer.inputs];
+            let sum: f32 = row.iter().zip(act.iter()).map(|(w, a)| w * a).sum();
+            next[n * width + out] = (sum + bias).max(0.0);
+          }
+        }
+        ::std::mem::swap(acts, next);
+      }
+
+      for ((score, act), board) in scores.iter_mut().zip(acts.chunks(width)).zip(boards.iter()) {
+        *score = 1.0 + act[0];
+        if self.future_points {
+          *score += board.earned_points() as f32;
+        }
+      }
+    });
+  }
+}
+
+impl<B: GameBoard> Evaluator<B> for Mlp {
+  const BATCHED: bool = true;
+
+  fn evaluate(&self, board: B) -> f32 {
+    let mut score = [0f32];
+    self.run(&[board], &mut score);
+    score[0]
+  }
+
+  fn evaluate_batch(&self, boards: &[B], scores: &mut [f32]) {
+    for (batch, batch_scores) in boards.chunks(MAX_BATCH).zip(scores.chunks_mut(MAX_BATCH)) {
+      self.run(batch, batch_scores);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use byteorder::WriteBytesExt;
+  use board::Board;
+  use std::io::Write;
+
+  #[test]
+  fn load_and_run() {
+    // Two hidden units, one adding up the ranks and one subtracting them,
+    // and an output of twice the first.
+    let mut data = Vec::new();
+    data.write_all(MAGIC).unwrap();
+    data.write_u8(VERSION).unwrap();
+    data.write_u8(0).unwrap();
+    data.write_f32::<LittleEndian>(2.0).unwrap();
+    data.write_u32::<LittleEndian>(2).unwrap();
+    for size in [16, 2, 1].iter() {
+      data.write_u32::<LittleEndian>(*size).unwrap();
+    }
+    for weight in (0..16).map(|_| 1.0).chain((0..16).map(|_| -1.0)).chain([0.5, 0.0, 2.0, 0.0, 0.0].iter().cloned()) {
+      data.write_f32::<LittleEndian>(weight).unwrap();
+    }
+
+    let path = ::std::env::temp_dir().join(format!("mlp-test-{}", ::std::process::id()));
+    let path = path.to_str().unwrap();
+    ::std::fs::write(path, &data).unwrap();
+    let mlp = Mlp::load(path);
+    ::std::fs::write(path, &data[..data.len() - 4]).unwrap();
+    let truncated = Mlp::load(path);
+    ::std::fs::remove_file(path).unwrap();
+    let mlp = mlp.unwrap();
+    assert!(truncated.is_err());
+    assert_eq!(mlp.inputs(), 16);
+
+    // Ranks add up to 9, halved by the input divisor.
+    let boards = [Board(0x0001_0020_0300_1002), Board(0), Board(0x1111_1111_1111_1111)];
+    assert_eq!(mlp.evaluate(boards[0]), 1.0 + 2.0 * (0.5 + 4.5));
+    let mut scores = [0f32; 3];
+    mlp.evaluate_batch(&boards, &mut scores);
+    assert_eq!(scores, [11.0, 2.0, 18.0]);
+  }
+}
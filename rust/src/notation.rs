@@ -0,0 +1,105 @@
+use std::fmt;
+use board::GameBoard;
+
+// Text form of boards, used by the Display and FromStr implementations of
+// the board types. Boards are written as rows of tile values, top row first,
+// such as "2 4 0 8/0 0 0 2/0 0 0 0/0 0 0 0". The alternate form ("{:#}")
+// is the board's bits in hex, with one group of digits per row so that it
+// can be pasted into a Board(0x...) in a test.
+
+pub fn format<B: GameBoard>(board: B, f: &mut fmt::Formatter) -> fmt::Result {
+  let width = B::WIDTH;
+  if f.alternate() {
+    let row_digits = (width as u32 * B::TILE_BITS).div_ceil(4) as usize;
+    let digits = format!("{:01$x}", board.bits(), row_digits * width as usize);
+    let rows: Vec<&str> = (0..width as usize).map(|row| &digits[row * row_digits..(row + 1) * row_digits]).collect();
+    return write!(f, "0x{}", rows.join("_"));
+  }
+
+  for row in (0..width).rev() {
+    if row != width - 1 {
+      write!(f, "/")?;
+    }
+    for col in (0..width).rev() {
+      let rank = board.get_tile(row * width + col);
+      let val = if rank == 0 { 0 } else { 1u64 << rank };
+      write!(f, "{}{}", val, if col == 0 { "" } else { " " })?;
+    }
+  }
+  Ok(())
+}
+
+pub fn parse<B: GameBoard>(s: &str) -> Result<B, String> {
+  let s = s.trim();
+  let width = B::WIDTH;
+  if let Some(hex) = s.strip_prefix("0x") {
+    let digits: String = hex.chars().filter(|c| *c != '_').collect();
+    let bits = u128::from_str_radix(&digits, 16).map_err(|e| format!("Invalid board \"{}\": {}", s, e))?;
+    let board_bits = (B::tiles() as u32) * B::TILE_BITS;
+    if board_bits < 128 && bits >> board_bits != 0 {
+      return Err(format!("Invalid board \"{}\": too many digits for a {1}x{1} board", s, width));
+    }
+    return Ok(B::from_bits(bits));
+  }
+
+  let rows: Vec<&str> = s.split('/').collect();
+  if rows.len() != width as usize {
+    return Err(format!("Invalid board \"{}\": expected {} rows", s, width));
+  }
+  let mut board = B::from_bits(0);
+  for (row, row_str) in rows.iter().enumerate() {
+    let vals: Vec<&str> = row_str.split_whitespace().collect();
+    if vals.len() != width as usize {
+      return Err(format!("Invalid board \"{}\": expected {} tiles in row \"{}\"", s, width, row_str));
+    }
+    for (col, val_str) in vals.iter().enumerate() {
+      let rank = match val_str.parse::<u64>() {
+        Ok(0) => 0,
+        Ok(val) if val >= 2 && val.is_power_of_two() && val.trailing_zeros() as i32 <= B::MAX_RANK =>
+          val.trailing_zeros() as i32,
+        _ => return Err(format!("Invalid board \"{}\": bad tile \"{}\"", s, val_str)),
+      };
+      if rank != 0 {
+        let tile = (width - 1 - row as i32) * width + width - 1 - col as i32;
+        board = board.set_tile(tile, rank);
+      }
+    }
+  }
+  Ok(board)
+}
+
+#[cfg(test)]
+mod tests {
+  use board::{Board, GameBoard};
+  use sized::SizedBoard;
+  use wide::WideBoard;
+
+  #[test]
+  fn round_trip() {
+    let board = Board(0x0001_0020_0300_100b);
+    assert_eq!(format!("{}", board), "0 0 0 2/0 0 4 0/0 8 0 0/2 0 0 2048");
+    assert_eq!(format!("{:#}", board), "0x0001_0020_0300_100b");
+    assert_eq!("0 0 0 2/0 0 4 0/0 8 0 0/2 0 0 2048".parse::<Board>(), Ok(board));
+    assert_eq!("0x0001_0020_0300_100b".parse::<Board>(), Ok(board));
+    assert_eq!("0x1002".parse::<Board>(), Ok(Board(0x1002)));
+
+    let sized = SizedBoard::<3>(0x012_110_303);
+    assert_eq!(format!("{}", sized), "0 2 4/2 2 0/8 0 8");
+    assert_eq!(format!("{:#}", sized), "0x012_110_303");
+    assert_eq!(format!("{}", sized).parse::<SizedBoard<3>>(), Ok(sized));
+
+    let wide = WideBoard(0).set_tile(15, 20).set_tile(0, 1);
+    assert_eq!(format!("{}", wide), "1048576 0 0 0/0 0 0 0/0 0 0 0/0 0 0 2");
+    assert_eq!(format!("{:#}", wide).parse::<WideBoard>(), Ok(wide));
+  }
+
+  #[test]
+  fn errors() {
+    assert!("0 0 0 2/0 0 4 0/0 8 0 0".parse::<Board>().is_err());
+    assert!("0 0 0 2/0 0 4 0/0 8 0 0/2 0 0".parse::<Board>().is_err());
+    assert!("0 0 0 3/0 0 4 0/0 8 0 0/2 0 0 2".parse::<Board>().is_err());
+    assert!("0 0 0 65536/0 0 4 0/0 8 0 0/2 0 0 2".parse::<Board>().is_err());
+    assert!("0x1_0000_0000_0000_0000".parse::<Board>().is_err());
+    assert!("0xfoo".parse::<Board>().is_err());
+  }
+}
@@ -0,0 +1,245 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::marker::PhantomData;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use board::{Board, GameBoard};
+use eval::Evaluator;
+use sized::SizedBoard;
+use wide::WideBoard;
+
+// The most tuples any supported board has: 5 rows, 5 columns and 16 squares.
+pub const MAX_TUPLES: usize = 26;
+
+pub type VPos = [u32; MAX_TUPLES];
+
+// Boards the n-tuple network can be used with. The tuples are every row,
+// every column and every 2x2 square of the board, in that order, each
+// indexed by the 4 bit ranks of its tiles.
+pub trait Tuples: GameBoard {
+  fn n_tuples() -> usize {
+    let width = Self::WIDTH as usize;
+    2 * width + (width - 1) * (width - 1)
+  }
+
+  // Number of entries in the table for tuple `n`.
+  fn tuple_size(n: usize) -> usize {
+    if n < 2 * Self::WIDTH as usize {
+      1 << (4 * Self::WIDTH)
+    } else {
+      1 << 16
+    }
+  }
+
+  fn vpos(self) -> VPos {
+    let width = Self::WIDTH;
+    // Ranks that don't fit in 4 bits share the entries for 32768.
+    let rank = |board: Self, row: i32, col: i32| ::std::cmp::min(board.get_tile(row * width + col), 15) as u32;
+
+    let mut res : VPos = [0; MAX_TUPLES];
+    let mut n = 0;
+    for board in [self, self.transpose()].iter() {
+      for row in 0..width {
+        res[n] = (0..width).fold(0, |pos, col| pos | rank(*board, row, col) << (4 * col));
+        n += 1;
+      }
+    }
+    for row in 0..width - 1 {
+      for col in 0..width - 1 {
+        res[n] = rank(self, row, col) |
+                 rank(self, row, col + 1) << 4 |
+                 rank(self, row + 1, col) << 8 |
+                 rank(self, row + 1, col + 1) << 12;
+        n += 1;
+      }
+    }
+    res
+  }
+}
+
+impl Tuples for Board {
+  fn vpos(self) -> VPos {
+    let mut res : VPos = [0; MAX_TUPLES];
+    // First the horizontal positions
+    for (i, pos) in res.iter_mut().take(4).enumerate() {
+      *pos = ((self.0 >> (16 * i)) & 0xffff) as u32;
+    }
+
+    // Then vertical
+    let t = self.transpose();
+    for (i, pos) in res[4..8].iter_mut().enumerate() {
+      *pos = ((t.0 >> (16 * i)) & 0xffff) as u32;
+    }
+
+    // Then squares
+    let mut b1 = self.0;
+    let mut b2 = self.0 >> 8;
+    let mut n = 8;
+    for _ in 0..3 {
+      for _ in 0..3 {
+        res[n] = ((b1 & 0xff) | (b2 & 0xff00)) as u32;
+        n += 1;
+        b1 >>= 4;
+        b2 >>= 4;
+      }
+      b1 >>= 4;
+      b2 >>= 4;
+    }
+
+    res
+  }
+}
+
+impl Tuples for WideBoard {}
+
+impl<const N: i32> Tuples for SizedBoard<N> {}
+
+// Table files start with this, followed by a version byte and the board
+// width, and then hold the tables as little endian f32s.
+const MAGIC: &[u8; 8] = b"2048NTUP";
+const VERSION: u8 = 1;
+
+// Value function made up of one table per tuple of the board, indexed by the
+// tiles in that tuple. The value of a position after a move is the points
+// still to be earned from it.
+pub struct NTuple<B> {
+  tables: Vec<f32>,
+  offsets: Vec<usize>,
+  board_type: PhantomData<B>,
+}
+
+impl<B: Tuples> Default for NTuple<B> {
+  fn default() -> NTuple<B> {
+    NTuple::new()
+  }
+}
+
+impl<B: Tuples> NTuple<B> {
+  pub fn new() -> NTuple<B> {
+    let mut offsets = Vec::with_capacity(B::n_tuples());
+    let mut size = 0;
+    for n in 0..B::n_tuples() {
+      offsets.push(size);
+      size += B::tuple_size(n);
+    }
+    NTuple { tables: vec![0f32; size], offsets, board_type: PhantomData }
+  }
+
+  fn sum(&self, vpos: &VPos) -> f32 {
+    self.offsets.iter().zip(vpos.iter()).map(|(offset, pos)| unsafe {
+      self.tables.get_unchecked(offset + *pos as usize)
+    }).sum()
+  }
+
+  // Symmetric boards share their entries, which makes every game teach the
+  // network about all orientations of its positions.
+  #[cfg(not(feature = "best-symmetry"))]
+  pub fn get_val(&self, board: B) -> (VPos, f32) {
+    let vpos = board.canonical().0.vpos();
+    (vpos, self.sum(&vpos))
+  }
+
+  #[cfg(feature = "best-symmetry")]
+  pub fn get_val(&self, board: B) -> (VPos, f32) {
+    let mut bestvpos = [0; MAX_TUPLES];
+    let mut bestval = ::std::f32::NEG_INFINITY;
+    for symm in board.symmetries() {
+      let vpos = symm.vpos();
+      let val = self.sum(&vpos);
+      if val > bestval {
+        bestval = val;
+        bestvpos = vpos;
+      }
+    }
+    (bestvpos, bestval)
+  }
+
+  pub fn adjust(&mut self, vpos: &VPos, adjust: f32) {
+    for (offset, pos) in self.offsets.iter().zip(vpos.iter()) {
+      unsafe { *self.tables.get_unchecked_mut(offset + *pos as usize) += adjust; }
+    }
+  }
+
+  pub fn save(&self, filename: &str) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(filename)?);
+    file.write_all(MAGIC)?;
+    file.write_u8(VERSION)?;
+    file.write_u8(B::WIDTH as u8)?;
+    for val in self.tables.iter() {
+      file.write_f32::<LittleEndian>(*val)?;
+    }
+    file.flush()
+  }
+
+  // Tables can be loaded for any board of the width they were saved for.
+  pub fn load(filename: &str) -> io::Result<NTuple<B>> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", filename, msg));
+    let mut file = BufReader::new(File::open(filename)?);
+    let mut magic = [0u8; 8];
+    file.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+      return Err(invalid("not an n-tuple table file".to_string()));
+    }
+    let version = file.read_u8()?;
+    if version != VERSION {
+      return Err(invalid(format!("unsupported n-tuple table version {}", version)));
+    }
+    let width = file.read_u8()? as i32;
+    if width != B::WIDTH {
+      return Err(invalid(format!("tables are for {0}x{0} boards", width)));
+    }
+
+    let mut res = NTuple::new();
+    file.read_f32_into::<LittleEndian>(&mut res.tables)?;
+    if file.read(&mut [0u8])? != 0 {
+      return Err(invalid("trailing data".to_string()));
+    }
+    Ok(res)
+  }
+}
+
+// Positions are compared by the points the tables expect still to come.
+// The points earned so far aren't added, since they can only be told from
+// the board by counting every 4 as merged, which would make outcomes
+// spawning a 4 score 4 points more than those spawning a 2.
+impl<B: Tuples> Evaluator<B> for NTuple<B> {
+  fn evaluate(&self, board: B) -> f32 {
+    1.0 + self.get_val(board).1.max(0.0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn generic_vpos() {
+    let boards = [0x0001_0020_0300_1001u64, 0x530c_50ac_53a0_03ac, 0x1234_5678_9abc_def0];
+    for b in boards.iter() {
+      let board = Board(*b);
+      let generic = SizedBoard::<4>(*b as u128);
+      assert_eq!(board.vpos(), generic.vpos());
+    }
+    assert_eq!(NTuple::<Board>::new().tables.len(), 17 * 65536);
+    assert_eq!(NTuple::<SizedBoard<3>>::new().tables.len(), 6 * 4096 + 4 * 65536);
+  }
+
+  #[test]
+  fn save_load() {
+    let board = Board(0x0001_0020_0300_1001);
+    let mut ntuple = NTuple::<Board>::new();
+    let (vpos, _) = ntuple.get_val(board);
+    ntuple.adjust(&vpos, 2.5);
+    assert_eq!(ntuple.get_val(board).1, 2.5 * 17.0);
+    // Only the points still to come count, whatever the tiles on the board.
+    assert_eq!(ntuple.evaluate(board), 1.0 + 2.5 * 17.0);
+
+    let path = ::std::env::temp_dir().join(format!("ntuple-test-{}", ::std::process::id()));
+    let path = path.to_str().unwrap();
+    ntuple.save(path).unwrap();
+    let loaded = NTuple::<Board>::load(path);
+    let wrong_width = NTuple::<SizedBoard<3>>::load(path);
+    ::std::fs::remove_file(path).unwrap();
+    assert_eq!(loaded.unwrap().get_val(board).1, 2.5 * 17.0);
+    assert_eq!(wrong_width.err().unwrap().kind(), io::ErrorKind::InvalidData);
+  }
+}
@@ -0,0 +1,164 @@
+use board::Board;
+use direction::Direction;
+
+// A straightforward implementation of the board logic, to test the table
+// driven Board against. Nothing here is meant to be fast.
+//
+// The grid is indexed as [row][col], with the top row and left column first,
+// the same way boards are written out by the notation module. Tiles hold
+// ranks, 0 for empty.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Grid(pub [[u8; 4]; 4]);
+
+// Board can't store ranks above 15. Two tiles of rank 15 still merge, but
+// the result stays at 15 and earns no points.
+const MAX_RANK: u8 = 15;
+
+// Slides a line of tiles towards index 0, merging each pair of equal tiles
+// once. Returns the new line and the points earned.
+pub fn slide_line(line: [u8; 4]) -> ([u8; 4], u32) {
+  let mut tiles = [0u8; 4];
+  let mut len = 0;
+  for rank in line.iter().filter(|rank| **rank != 0) {
+    tiles[len] = *rank;
+    len += 1;
+  }
+
+  let mut res = [0u8; 4];
+  let mut reward = 0;
+  let mut n = 0;
+  let mut i = 0;
+  while i < len {
+    if i + 1 < len && tiles[i] == tiles[i + 1] {
+      if tiles[i] == MAX_RANK {
+        res[n] = MAX_RANK;
+      } else {
+        res[n] = tiles[i] + 1;
+        reward += 1 << (tiles[i] + 1);
+      }
+      i += 2;
+    } else {
+      res[n] = tiles[i];
+      i += 1;
+    }
+    n += 1;
+  }
+  (res, reward)
+}
+
+impl Grid {
+  pub fn from_board(board: Board) -> Grid {
+    let mut grid = [[0u8; 4]; 4];
+    for (row, cells) in grid.iter_mut().enumerate() {
+      for (col, cell) in cells.iter_mut().enumerate() {
+        *cell = board.get_tile(15 - 4 * row as i32 - col as i32) as u8;
+      }
+    }
+    Grid(grid)
+  }
+
+  pub fn to_board(self) -> Board {
+    let mut board = Board(0);
+    for row in 0..4 {
+      for col in 0..4 {
+        board.0 |= (self.0[row][col] as u64) << (4 * (15 - 4 * row - col));
+      }
+    }
+    board
+  }
+
+  // The line of tiles in row or column `n` which is slid in `dir`, starting
+  // with the tile the line is slid towards, as (row, col) positions.
+  fn line(n: usize, dir: Direction) -> [(usize, usize); 4] {
+    let mut res = [(0, 0); 4];
+    for (i, pos) in res.iter_mut().enumerate() {
+      *pos = match dir {
+        Direction::Right => (n, 3 - i),
+        Direction::Down => (3 - i, n),
+        Direction::Left => (n, i),
+        Direction::Up => (i, n),
+      };
+    }
+    res
+  }
+
+  pub fn slide(self, dir: Direction) -> (Grid, u32) {
+    let mut res = Grid([[0; 4]; 4]);
+    let mut reward = 0;
+    for n in 0..4 {
+      let positions = Grid::line(n, dir);
+      let mut line = [0u8; 4];
+      for (tile, &(row, col)) in line.iter_mut().zip(positions.iter()) {
+        *tile = self.0[row][col];
+      }
+      let (slid, line_reward) = slide_line(line);
+      for (tile, &(row, col)) in slid.iter().zip(positions.iter()) {
+        res.0[row][col] = *tile;
+      }
+      reward += line_reward;
+    }
+    (res, reward)
+  }
+
+  pub fn transpose(self) -> Grid {
+    let mut res = [[0u8; 4]; 4];
+    for (row, cells) in res.iter_mut().enumerate() {
+      for (col, cell) in cells.iter_mut().enumerate() {
+        *cell = self.0[col][row];
+      }
+    }
+    Grid(res)
+  }
+
+  // Turns the grid upside down, like Board::flip_horiz.
+  pub fn flip_horiz(self) -> Grid {
+    let mut res = self.0;
+    res.reverse();
+    Grid(res)
+  }
+
+  // Mirrors the grid left to right, like Board::flip_vert.
+  pub fn flip_vert(self) -> Grid {
+    let mut res = self.0;
+    for row in res.iter_mut() {
+      row.reverse();
+    }
+    Grid(res)
+  }
+
+  // The points earned by the merges needed to reach the grid, like
+  // GameBoard::earned_points.
+  pub fn earned_points(self) -> u64 {
+    let mut score = 0;
+    for rank in self.0.iter().flat_map(|row| row.iter()) {
+      if *rank >= 2 {
+        score += (*rank as u64 - 1) << *rank;
+      }
+    }
+    score
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn lines() {
+    assert_eq!(slide_line([1, 1, 1, 1]), ([2, 2, 0, 0], 8));
+    assert_eq!(slide_line([0, 2, 0, 2]), ([3, 0, 0, 0], 8));
+    assert_eq!(slide_line([1, 1, 2, 0]), ([2, 2, 0, 0], 4));
+    assert_eq!(slide_line([15, 15, 15, 0]), ([15, 15, 0, 0], 0));
+
+    let grid = Grid([[0, 0, 0, 1],
+                     [0, 0, 2, 0],
+                     [0, 3, 0, 0],
+                     [1, 0, 0, 11]]);
+    assert_eq!(grid.to_board(), Board(0x0001_0020_0300_100b));
+    assert_eq!(Grid::from_board(grid.to_board()), grid);
+    assert_eq!(grid.slide(Direction::Up).0, Grid([[1, 3, 2, 1],
+                                                  [0, 0, 0, 11],
+                                                  [0, 0, 0, 0],
+                                                  [0, 0, 0, 0]]));
+  }
+}
@@ -0,0 +1,189 @@
+use std::io::{self, IsTerminal, Write};
+use board::GameBoard;
+
+// A board prepared for drawing, together with the score and any extra lines
+// of text to show below it.
+pub struct Frame<'a> {
+  // Ranks of the tiles, row by row starting in the top left corner.
+  pub ranks: Vec<i32>,
+  pub width: usize,
+  // Width of the widest label the board type can show.
+  pub cell_width: usize,
+  pub score: u32,
+  pub extra: &'a str,
+}
+
+impl<'a> Frame<'a> {
+  pub fn new<B: GameBoard>(board: B, score: u32, extra: &'a str) -> Frame<'a> {
+    Frame {
+      ranks: (0..B::tiles()).map(|n| board.get_tile(B::tiles() - 1 - n)).collect(),
+      width: B::WIDTH as usize,
+      cell_width: (1..B::MAX_RANK + 1).map(|rank| tile_label(rank).len()).max().unwrap(),
+      score,
+      extra,
+    }
+  }
+}
+
+pub trait Renderer {
+  fn draw(&mut self, out: &mut dyn Write, frame: &Frame) -> io::Result<()>;
+
+  fn show(&mut self, frame: &Frame) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    self.draw(&mut out, frame)?;
+    out.flush()
+  }
+}
+
+// Short form of the value of a tile of the given rank, such as "128" or "4K".
+pub fn tile_label(rank: i32) -> String {
+  if rank == 0 {
+    return String::new();
+  }
+  let val = 1u64 << rank;
+  if val < 1 << 10 {
+    format!("{}", val)
+  } else if val < 1 << 20 {
+    format!("{}K", val >> 10)
+  } else if val < 1 << 30 {
+    format!("{}M", val >> 20)
+  } else {
+    format!("{}G", val >> 30)
+  }
+}
+
+// Draws the grid, calling `cell` to write the label of each tile.
+fn draw_grid<F>(out: &mut dyn Write, frame: &Frame, cell: F) -> io::Result<()>
+  where F: Fn(&mut dyn Write, i32, &str) -> io::Result<()> {
+  let line = format!("+{}", format!("{}+", "-".repeat(frame.cell_width)).repeat(frame.width));
+  writeln!(out, "{}", line)?;
+  for row in frame.ranks.chunks(frame.width) {
+    for rank in row.iter() {
+      write!(out, "|")?;
+      cell(out, *rank, &format!("{:>1$}", tile_label(*rank), frame.cell_width))?;
+    }
+    writeln!(out, "|\n{}", line)?;
+  }
+  writeln!(out, "Score: {}", frame.score)?;
+  write!(out, "{}", frame.extra)
+}
+
+// Plain text which can be written to a log or a file.
+pub struct PlainRenderer;
+
+impl Renderer for PlainRenderer {
+  fn draw(&mut self, out: &mut dyn Write, frame: &Frame) -> io::Result<()> {
+    draw_grid(out, frame, |out, _, label| write!(out, "{}", label))
+  }
+}
+
+// Background colours from the 256 colour palette for each rank, repeating
+// for ranks past the end.
+const RANK_COLOURS: [u8; 16] = [255, 230, 215, 209, 203, 196, 228, 227, 226, 220, 214, 135, 99, 63, 27, 21];
+
+// Tiles in ANSI colours, with a colour per rank.
+pub struct ColourRenderer;
+
+impl Renderer for ColourRenderer {
+  fn draw(&mut self, out: &mut dyn Write, frame: &Frame) -> io::Result<()> {
+    draw_grid(out, frame, |out, rank, label| {
+      if rank == 0 {
+        return write!(out, "{}", label);
+      }
+      let background = RANK_COLOURS[(rank as usize - 1) % RANK_COLOURS.len()];
+      // Dark text on the light colours.
+      let foreground = if rank <= 2 || (7..=10).contains(&rank) { 235 } else { 255 };
+      write!(out, "\x1b[1;38;5;{};48;5;{}m{}\x1b[0m", foreground, background, label)
+    })
+  }
+}
+
+// Draws each frame over the previous one, for following a game in a
+// terminal. Anything else printed between two frames is overwritten.
+pub struct RedrawRenderer {
+  inner: Box<dyn Renderer>,
+  lines: usize,
+}
+
+impl RedrawRenderer {
+  pub fn new(inner: Box<dyn Renderer>) -> RedrawRenderer {
+    RedrawRenderer { inner, lines: 0 }
+  }
+}
+
+impl Renderer for RedrawRenderer {
+  fn draw(&mut self, out: &mut dyn Write, frame: &Frame) -> io::Result<()> {
+    let mut buf = Vec::new();
+    self.inner.draw(&mut buf, frame)?;
+    if self.lines != 0 {
+      write!(out, "\x1b[{}A", self.lines)?;
+    }
+    let text = String::from_utf8_lossy(&buf);
+    // Clear what is left of each line, and of the screen below, from the
+    // previous frame.
+    for line in text.lines() {
+      writeln!(out, "{}\x1b[K", line)?;
+    }
+    write!(out, "\x1b[J")?;
+    self.lines = text.lines().count();
+    Ok(())
+  }
+}
+
+pub const NAMES: &str = "plain, colour, redraw or redraw-colour";
+
+pub fn from_name(name: &str) -> Result<Box<dyn Renderer>, String> {
+  match name {
+    "plain" => Ok(Box::new(PlainRenderer)),
+    "colour" => Ok(Box::new(ColourRenderer)),
+    "redraw" => Ok(Box::new(RedrawRenderer::new(Box::new(PlainRenderer)))),
+    "redraw-colour" => Ok(Box::new(RedrawRenderer::new(Box::new(ColourRenderer)))),
+    _ => Err(format!("Unknown renderer: {}", name)),
+  }
+}
+
+// Redraws in place when writing to a terminal, and otherwise writes plain
+// text.
+pub fn default_renderer() -> Box<dyn Renderer> {
+  if io::stdout().is_terminal() {
+    Box::new(RedrawRenderer::new(Box::new(PlainRenderer)))
+  } else {
+    Box::new(PlainRenderer)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use board::Board;
+  use sized::SizedBoard;
+
+  #[test]
+  fn plain() {
+    let mut out = Vec::new();
+    PlainRenderer.draw(&mut out, &Frame::new(SizedBoard::<3>(0x012_110_30b), 20, "Moves: 3\n")).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "+---+---+---+\n\
+                |   |  2|  4|\n\
+                +---+---+---+\n\
+                |  2|  2|   |\n\
+                +---+---+---+\n\
+                |  8|   | 2K|\n\
+                +---+---+---+\n\
+                Score: 20\n\
+                Moves: 3\n");
+  }
+
+  #[test]
+  fn redraw() {
+    let mut out = Vec::new();
+    let mut renderer = RedrawRenderer::new(Box::new(PlainRenderer));
+    renderer.draw(&mut out, &Frame::new(Board(0x1), 0, "")).unwrap();
+    let first = String::from_utf8(out.clone()).unwrap();
+    assert!(!first.contains("\x1b[10A"));
+    assert_eq!(first.matches('\n').count(), 10);
+    renderer.draw(&mut out, &Frame::new(Board(0x2), 4, "")).unwrap();
+    assert!(String::from_utf8(out).unwrap()[first.len()..].starts_with("\x1b[10A"));
+  }
+}
@@ -0,0 +1,176 @@
+use std;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use byteorder::{NativeEndian, WriteBytesExt, ReadBytesExt};
+use board::GameBoard;
+use direction::Direction;
+
+// One position of a played game together with what the search decided.
+#[derive(Debug, Copy, Clone)]
+pub struct Record<B> {
+  pub board: B,
+  pub score: u32,
+  pub exp: f32,
+  pub end_prob: f32,
+  // None at the end of the game.
+  pub dir: Option<Direction>,
+  pub depth: u8,
+  pub searches: u8,
+}
+
+// Replay files start with this, followed by a version byte and the board
+// width and tile size. Files without it are from before boards other than
+// the 4 bit 4x4 board existed, and store each board as a u64 followed by the
+// number of 4s spawned rather than the score.
+const MAGIC: &[u8; 8] = b"2048RPLY";
+const VERSION: u8 = 1;
+
+const LEGACY_RECORD_SIZE: u64 = 23;
+
+pub struct ReplayWriter<B> {
+  file: BufWriter<File>,
+  board_type: PhantomData<B>,
+}
+
+impl<B: GameBoard> ReplayWriter<B> {
+  pub fn create(filename: &str) -> io::Result<ReplayWriter<B>> {
+    let mut file = BufWriter::new(File::create(filename)?);
+    file.write_all(MAGIC)?;
+    file.write_u8(VERSION)?;
+    file.write_u8(B::WIDTH as u8)?;
+    file.write_u8(B::TILE_BITS as u8)?;
+    Ok(ReplayWriter { file, board_type: PhantomData })
+  }
+
+  pub fn write(&mut self, record: &Record<B>) -> io::Result<()> {
+    self.file.write_u128::<NativeEndian>(record.board.bits())?;
+    self.file.write_u32::<NativeEndian>(record.score)?;
+    self.file.write_f32::<NativeEndian>(record.exp)?;
+    self.file.write_f32::<NativeEndian>(record.end_prob)?;
+    self.file.write_i8(record.dir.map_or(-1, |dir| dir.index() as i8))?;
+    self.file.write_u8(record.depth)?;
+    self.file.write_u8(record.searches)
+  }
+}
+
+// Returns the width and bits per tile of the boards stored in a replay file,
+// leaving the file positioned at the first record.
+fn read_header(f: &mut File) -> io::Result<(i32, u32, bool)> {
+  let mut magic = [0u8; 8];
+  if f.read_exact(&mut magic).is_ok() && &magic == MAGIC {
+    let version = f.read_u8()?;
+    if version != VERSION {
+      return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                format!("unsupported replay version {}", version)));
+    }
+    let width = f.read_u8()? as i32;
+    let tile_bits = f.read_u8()? as u32;
+    Ok((width, tile_bits, false))
+  } else {
+    f.seek(SeekFrom::Start(0))?;
+    Ok((4, 4, true))
+  }
+}
+
+pub fn board_type(filename: &str) -> io::Result<(i32, u32)> {
+  let (width, tile_bits, _) = read_header(&mut File::open(filename)?)?;
+  Ok((width, tile_bits))
+}
+
+// The score of a game which reached `board` with `fours` of the tiles spawned
+// being 4s, which were never merged to get.
+fn legacy_score<B: GameBoard>(board: B, fours: i32) -> u32 {
+  std::cmp::max(0, board.earned_points() as i64 - 4 * fours as i64) as u32
+}
+
+pub fn read<B: GameBoard>(filename: &str) -> io::Result<Vec<Record<B>>> {
+  let mut file = File::open(filename)?;
+  let len = file.metadata()?.len();
+  let (width, tile_bits, legacy) = read_header(&mut file)?;
+  if width != B::WIDTH || tile_bits != B::TILE_BITS {
+    return Err(io::Error::new(io::ErrorKind::InvalidData,
+                              format!("replay is for a {0}x{0} board with {1} bit tiles", width, tile_bits)));
+  }
+
+  let mut f = BufReader::new(file);
+  let mut records = Vec::new();
+  loop {
+    let board = if legacy {
+      if records.len() as u64 == len / LEGACY_RECORD_SIZE {
+        break;
+      }
+      f.read_u64::<NativeEndian>()? as u128
+    } else {
+      match f.read_u128::<NativeEndian>() {
+        Ok(bits) => bits,
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+        Err(e) => return Err(e),
+      }
+    };
+    let score = if legacy {
+      legacy_score(B::from_bits(board), f.read_i32::<NativeEndian>()?)
+    } else {
+      f.read_u32::<NativeEndian>()?
+    };
+    let exp = f.read_f32::<NativeEndian>()?;
+    let end_prob = f.read_f32::<NativeEndian>()?;
+    let dir = match f.read_i8()? {
+      -1 => None,
+      n => match Direction::from_index(n as usize) {
+        Some(dir) => Some(dir),
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid direction {}", n))),
+      },
+    };
+    records.push(Record {
+                   board: B::from_bits(board),
+                   score,
+                   exp,
+                   end_prob,
+                   dir,
+                   depth: f.read_u8()?,
+                   searches: f.read_u8()?,
+                 });
+  }
+  Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use board::Board;
+
+  #[test]
+  fn legacy() {
+    // Records as written before replays had a header, with the number of 4s
+    // spawned where the score is now.
+    let mut data = Vec::new();
+    for (bits, fours, dir) in [(0x0000_0000_0000_1123u64, 1, 0i8), (0x0000_0000_0001_0133, 1, -1)].iter() {
+      data.write_u64::<NativeEndian>(*bits).unwrap();
+      data.write_i32::<NativeEndian>(*fours).unwrap();
+      data.write_f32::<NativeEndian>(100.0).unwrap();
+      data.write_f32::<NativeEndian>(0.5).unwrap();
+      data.write_i8(*dir).unwrap();
+      data.write_u8(3).unwrap();
+      data.write_u8(1).unwrap();
+    }
+    assert_eq!(data.len() as u64, 2 * LEGACY_RECORD_SIZE);
+
+    let path = std::env::temp_dir().join(format!("replay-test-{}", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::write(path, &data).unwrap();
+    let board_type = board_type(path).unwrap();
+    let records = read::<Board>(path);
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(board_type, (4, 4));
+    let records = records.unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].board, Board(0x1123));
+    // Merging to an 8 and a 4 earns 20 points, less the 4 which was spawned.
+    assert_eq!(records[0].score, 16);
+    assert_eq!(records[0].dir, Direction::from_index(0));
+    assert_eq!((records[0].exp, records[0].end_prob, records[0].depth, records[0].searches), (100.0, 0.5, 3, 1));
+    assert_eq!(records[1].score, 28);
+    assert_eq!(records[1].dir, None);
+  }
+}
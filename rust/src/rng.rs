@@ -0,0 +1,70 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Source of randomness for tile spawns and exploration. Each game owns its
+// own generator so games are reproducible and can run on separate threads.
+pub trait Rng {
+  fn next_u32(&mut self) -> u32;
+
+  // Returns a number in 0..max.
+  fn gen_range(&mut self, max: u32) -> u32 {
+    debug_assert!(max > 0);
+    self.next_u32() % max
+  }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct XorShift {
+  state: u32,
+}
+
+pub const DEFAULT_SEED: u32 = 0x17004711;
+
+impl XorShift {
+  pub fn new(seed: u32) -> XorShift {
+    // An all-zero state would only ever produce zeros.
+    XorShift { state: if seed == 0 { DEFAULT_SEED } else { seed } }
+  }
+}
+
+impl Default for XorShift {
+  fn default() -> XorShift {
+    XorShift::new(DEFAULT_SEED)
+  }
+}
+
+impl Rng for XorShift {
+  fn next_u32(&mut self) -> u32 {
+    let mut x = self.state;
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    self.state = x;
+    x
+  }
+}
+
+pub fn seed_from_time() -> u32 {
+  let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+  (now.as_secs() as u32) ^ now.subsec_nanos()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reproducible() {
+    let mut a = XorShift::new(1234);
+    let mut b = XorShift::new(1234);
+    for _ in 0..100 {
+      assert_eq!(a.next_u32(), b.next_u32());
+    }
+    assert!(XorShift::new(1).next_u32() != XorShift::new(2).next_u32());
+  }
+
+  #[test]
+  fn zero_seed() {
+    let mut rng = XorShift::new(0);
+    assert!(rng.next_u32() != 0);
+  }
+}
@@ -0,0 +1,115 @@
+// Row lookup tables for the u128 based boards, which unlike Board can't use
+// tables built at compile time since their row size depends on the board.
+
+// Slides the ranks in `vals` towards index 0, merging equal neighbours. As
+// with Board, two tiles of `max_rank` merge into one of `max_rank`, which
+// earns no points. Returns the points earned.
+pub fn slide_ranks(vals: &mut [i32], max_rank: i32) -> u32 {
+  let mut n = 0;
+  let mut reward = 0u32;
+  let mut can_merge = false;
+  for pos in 0..vals.len() {
+    let val = vals[pos];
+    vals[pos] = 0;
+    if val == 0 {
+      continue;
+    }
+    if can_merge && vals[n - 1] == val {
+      if val < max_rank {
+        vals[n - 1] += 1;
+        // Only rows that can't occur in a real game would overflow.
+        reward = reward.saturating_add(1 << (val + 1));
+      }
+      can_merge = false;
+    } else {
+      vals[n] = val;
+      n += 1;
+      can_merge = true;
+    }
+  }
+  reward
+}
+
+// Indexed by a row, giving the slid row and the points it earned.
+pub struct RowTables {
+  width: i32,
+  bits: u32,
+  right: Vec<(u32, u32)>,
+  left: Vec<(u32, u32)>,
+}
+
+impl RowTables {
+  pub fn new(width: i32, bits: u32, max_rank: i32) -> RowTables {
+    let row_bits = width as u32 * bits;
+    debug_assert!(row_bits <= 25);
+    let mask = (1 << bits) - 1;
+    let reverse = |row: u32| (0..width as u32).fold(0, |res, pos| {
+      res | ((row >> (bits * pos)) & mask) << (bits * (width as u32 - 1 - pos))
+    });
+
+    let mut right = vec![(0, 0); 1 << row_bits];
+    let mut left = vec![(0, 0); 1 << row_bits];
+    let mut vals = vec![0; width as usize];
+    for n in 0..(1u32 << row_bits) {
+      for pos in 0..width as u32 {
+        vals[pos as usize] = ((n >> (bits * pos)) & mask) as i32;
+      }
+      let reward = slide_ranks(&mut vals, max_rank);
+      let res = vals.iter().enumerate().fold(0, |row, (pos, val)| row | (*val as u32) << (bits * pos as u32));
+      right[n as usize] = (res, reward);
+      left[reverse(n) as usize] = (reverse(res), reward);
+    }
+    RowTables { width, bits, right, left }
+  }
+
+  // Slides every row of `board` right or left, returning the new board and
+  // the points earned.
+  pub fn slide_rows(&self, board: u128, left: bool) -> (u128, u32) {
+    let row_bits = self.width as u32 * self.bits;
+    let mask = (1u128 << row_bits) - 1;
+    let table = if left { &self.left } else { &self.right };
+    let mut res = 0u128;
+    let mut reward = 0;
+    for row in 0..self.width as u32 {
+      let (new_row, row_reward) = table[((board >> (row_bits * row)) & mask) as usize];
+      res |= (new_row as u128) << (row_bits * row);
+      reward += row_reward;
+    }
+    (res, reward)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn ranks() {
+    let mut vals = [1, 1, 1, 0];
+    assert_eq!(slide_ranks(&mut vals, 15), 4);
+    assert_eq!(vals, [2, 1, 0, 0]);
+    let mut vals = [0, 3, 0, 3, 2];
+    assert_eq!(slide_ranks(&mut vals, 15), 16);
+    assert_eq!(vals, [4, 2, 0, 0, 0]);
+    let mut vals = [15, 15, 2];
+    assert_eq!(slide_ranks(&mut vals, 15), 0);
+    assert_eq!(vals, [15, 2, 0]);
+    let mut vals = [15, 15, 15, 15];
+    assert_eq!(slide_ranks(&mut vals, 15), 0);
+    assert_eq!(vals, [15, 15, 0, 0]);
+    let mut vals = [31, 31, 0, 30];
+    assert_eq!(slide_ranks(&mut vals, 31), 0);
+    assert_eq!(vals, [31, 30, 0, 0]);
+  }
+
+  #[test]
+  fn tables() {
+    let tables = RowTables::new(3, 4, 15);
+    assert_eq!(tables.slide_rows(0x011, false), (0x002, 4));
+    assert_eq!(tables.slide_rows(0x011, true), (0x200, 4));
+    assert_eq!(tables.slide_rows(0x120_101, false), (0x012_002, 4));
+    assert_eq!(tables.slide_rows(0x0ff, false), (0x00f, 0));
+    let wide = RowTables::new(4, 5, 31);
+    assert_eq!(wide.slide_rows(31 << 5 | 31, true), (31 << 15, 0));
+  }
+}
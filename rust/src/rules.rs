@@ -0,0 +1,114 @@
+use rng::Rng;
+
+// The rules for where and which tiles the computer places. Spawned tiles are
+// picked at random among the configured ranks, each with a probability of
+// its weight divided by the total weight, and placed on a random empty tile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ruleset {
+  spawns: Vec<(i32, u32)>,
+  probs: Vec<(i32, f32)>,
+  total_weight: u32,
+  // Number of tiles placed after every move.
+  pub spawns_per_turn: u32,
+  // Number of tiles placed on the empty board before the first move.
+  pub start_tiles: u32,
+}
+
+impl Ruleset {
+  pub fn new(spawns: Vec<(i32, u32)>, spawns_per_turn: u32, start_tiles: u32) -> Result<Ruleset, String> {
+    if spawns.is_empty() {
+      return Err("At least one spawn tile is needed".to_string());
+    }
+    if let Some(&(rank, _)) = spawns.iter().find(|&&(rank, _)| !(1..=15).contains(&rank)) {
+      return Err(format!("Unsupported spawn rank: {}", rank));
+    }
+    let total_weight: u32 = spawns.iter().map(|&(_, weight)| weight).sum();
+    if total_weight == 0 {
+      return Err("Spawn weights must not all be zero".to_string());
+    }
+    if spawns_per_turn == 0 || start_tiles == 0 {
+      return Err("At least one tile must be placed each turn and at the start".to_string());
+    }
+    let probs = spawns.iter().filter(|&&(_, weight)| weight > 0).map(|&(rank, weight)| (rank, weight as f32 / total_weight as f32)).collect();
+    Ok(Ruleset { spawns, probs, total_weight, spawns_per_turn, start_tiles })
+  }
+
+  // Ranks which can be spawned together with their weights.
+  pub fn spawns(&self) -> &[(i32, u32)] {
+    &self.spawns
+  }
+
+  // Ranks which can be spawned together with their probabilities.
+  pub fn spawn_probs(&self) -> &[(i32, f32)] {
+    &self.probs
+  }
+
+  pub fn spawn_rank<R: Rng>(&self, rng: &mut R) -> i32 {
+    // Walked from the end so that the standard rules pick a 4 exactly when
+    // games from before the rules were configurable did.
+    let mut n = rng.gen_range(self.total_weight);
+    for &(rank, weight) in self.spawns.iter().rev() {
+      if n < weight {
+        return rank;
+      }
+      n -= weight;
+    }
+    unreachable!()
+  }
+}
+
+impl Default for Ruleset {
+  // 2s with a probability of 0.9 and 4s with 0.1, one per move, starting
+  // with two tiles.
+  fn default() -> Ruleset {
+    Ruleset::new(vec![(1, 9), (2, 1)], 1, 2).unwrap()
+  }
+}
+
+// Parses spawn weights given as "value:weight" pairs such as "2:9,4:1".
+pub fn parse_spawns(spec: &str) -> Result<Vec<(i32, u32)>, String> {
+  spec.split(',').map(|pair| {
+    let mut parts = pair.split(':');
+    let value = parts.next().and_then(|v| v.trim().parse::<u32>().ok());
+    let weight = parts.next().and_then(|w| w.trim().parse::<u32>().ok());
+    match (value, weight, parts.next()) {
+      (Some(value), Some(weight), None) if value >= 2 && value.is_power_of_two() =>
+        Ok((value.trailing_zeros() as i32, weight)),
+      _ => Err(format!("Invalid spawn weight: {}", pair)),
+    }
+  }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rng::XorShift;
+
+  #[test]
+  fn spawns() {
+    assert_eq!(parse_spawns("2:9,4:1"), Ok(vec![(1, 9), (2, 1)]));
+    assert_eq!(parse_spawns("2:1"), Ok(vec![(1, 1)]));
+    assert!(parse_spawns("3:1").is_err());
+    assert!(parse_spawns("2:1:4").is_err());
+    assert!(Ruleset::new(vec![(1, 0)], 1, 2).is_err());
+
+    let rules = Ruleset::default();
+    assert_eq!(rules.spawn_probs(), &[(1, 0.9), (2, 0.1)]);
+
+    // The standard rules spawn the same tiles as before they were
+    // configurable.
+    let mut rng1 = XorShift::new(1);
+    let mut rng2 = XorShift::new(1);
+    for _ in 0..1000 {
+      assert_eq!(rules.spawn_rank(&mut rng1), if rng2.gen_range(10) == 0 { 2 } else { 1 });
+    }
+
+    let rules = Ruleset::new(vec![(1, 1), (3, 1)], 2, 3).unwrap();
+    let mut counts = [0; 4];
+    for _ in 0..1000 {
+      counts[rules.spawn_rank(&mut rng1) as usize] += 1;
+    }
+    assert_eq!(counts[0] + counts[2], 0);
+    assert!(counts[1] > 400 && counts[3] > 400);
+  }
+}
@@ -0,0 +1,751 @@
+use std;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use futures;
+use futures::Future;
+use futures_cpupool::CpuPool;
+use board::{GameBoard, SpawnOutcomes};
+use config::{Pruning, Sampling, SearchConfig};
+use direction::Direction;
+use eval::Evaluator;
+use heuristic::{Heuristic, HeuristicParams};
+use rng::{Rng, XorShift};
+use rules::Ruleset;
+use transtable::{Bound, Entry, TransTable};
+
+#[derive(Debug, Copy, Clone)]
+pub enum PlayState {
+  ZeroProbDeath,
+  LowProbDeath,
+  HighPropDeath,
+  VeryHighProbDeath,
+}
+
+impl PlayState {
+  pub fn from_prob(prob: f32, config: &SearchConfig) -> PlayState {
+    if prob > config.very_high_death_prob {
+      PlayState::VeryHighProbDeath
+    } else if prob > config.high_death_prob {
+      PlayState::HighPropDeath
+    } else if prob > 0.0 {
+      PlayState::LowProbDeath
+    } else {
+      PlayState::ZeroProbDeath
+    }
+  }
+
+  pub fn depth<B: GameBoard>(self, board: B, config: &SearchConfig) -> u8 {
+    let offset = match self {
+      PlayState::ZeroProbDeath => config.zero_death_offset,
+      PlayState::LowProbDeath => config.low_death_offset,
+      PlayState::HighPropDeath => config.high_death_offset,
+      PlayState::VeryHighProbDeath => return config.max_depth,
+    };
+    // Unlike clamp, doesn't panic on configs which weren't validated.
+    board.distinct().saturating_sub(offset).max(config.min_depth).min(config.max_depth)
+  }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct SearchResult {
+  // None if the game is over.
+  pub dir: Option<Direction>,
+  pub exp: f32,
+  pub end_prob: f32,
+  pub depth: u8,
+  pub searches: u32,
+  pub state: PlayState,
+  pub stats: SearchStats,
+}
+
+// Nodes searched and transposition table use, summed over searches.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SearchStats {
+  pub nodes: u64,
+  // Nodes whose result was found in the table.
+  pub hits: u64,
+}
+
+impl SearchStats {
+  pub fn add(&mut self, other: SearchStats) {
+    self.nodes += other.nodes;
+    self.hits += other.hits;
+  }
+
+  pub fn hit_rate(self) -> f64 {
+    if self.nodes == 0 { 0.0 } else { self.hits as f64 / self.nodes as f64 }
+  }
+}
+
+// Limits on how long searches may take. With a limit per move, each move is
+// searched one depth deeper at a time until the limit is reached, and the
+// move found by the deepest search which finished is played. The time for
+// the whole game only cuts searches short, so that once it is used up the
+// remaining moves are searched to depth 1. The first depth is always
+// searched to the end, whatever the limits.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Budget {
+  pub time_per_move: Option<Duration>,
+  pub nodes_per_move: Option<u64>,
+  pub time_per_game: Option<Duration>,
+}
+
+impl Budget {
+  fn per_move(&self) -> bool {
+    self.time_per_move.is_some() || self.nodes_per_move.is_some()
+  }
+
+  fn is_limited(&self) -> bool {
+    self.per_move() || self.time_per_game.is_some()
+  }
+}
+
+// Number of nodes a thread searches between checks of the budget.
+const NODES_PER_CHECK: u64 = 1024;
+
+// Tells the threads searching a move when its budget is used up.
+struct Stopper {
+  deadline: Option<Instant>,
+  max_nodes: Option<u64>,
+  nodes: AtomicU64,
+  stopped: AtomicBool,
+}
+
+impl Stopper {
+  fn add_nodes(&self, nodes: u64) {
+    let total = self.nodes.fetch_add(nodes, Ordering::Relaxed) + nodes;
+    if self.max_nodes.is_some_and(|max| total >= max) ||
+       self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+      self.stopped.store(true, Ordering::Relaxed);
+    }
+  }
+
+  fn is_stopped(&self) -> bool {
+    self.stopped.load(Ordering::Relaxed)
+  }
+}
+
+// What the search of one move needs, shared by all of its threads except
+// for the statistics and the buffers for batched evaluation.
+struct SearchContext<'a, B, E: 'a> {
+  table: &'a TransTable,
+  evaluator: &'a E,
+  rules: &'a Ruleset,
+  prob_cutoff: f32,
+  sampling: Sampling,
+  stats: SearchStats,
+  // None while the search may not be stopped.
+  stopper: Option<&'a Stopper>,
+  unchecked_nodes: u64,
+  // Only used when pruning.
+  upper_bound: f32,
+  leaves: Vec<B>,
+  scores: Vec<f32>,
+}
+
+impl<'a, B, E> SearchContext<'a, B, E> {
+  fn stopped(&mut self) -> bool {
+    match self.stopper {
+      None => false,
+      Some(stopper) => {
+        self.unchecked_nodes += 1;
+        if self.unchecked_nodes == NODES_PER_CHECK {
+          stopper.add_nodes(self.unchecked_nodes);
+          self.unchecked_nodes = 0;
+        }
+        stopper.is_stopped()
+      }
+    }
+  }
+}
+
+impl<'a, B: GameBoard, E> SearchContext<'a, B, E> {
+  // The outcomes searched of a chance node which is reached with probability
+  // `prob`.
+  fn spawn_outcomes(&self, board: B, prob: f32) -> SpawnOutcomes<'a, B> {
+    board.spawn_outcomes_in(self.rules, sample_cells(board, sample_count(board, prob, self.sampling)))
+  }
+}
+
+// How many of the empty tiles of `board` a chance node which is reached with
+// probability `prob` spawns on.
+fn sample_count<B: GameBoard>(board: B, prob: f32, sampling: Sampling) -> u32 {
+  let empty = board.empty() as u32;
+  match sampling {
+    Sampling::Full => empty,
+    Sampling::Fixed(n) => std::cmp::min(n, empty),
+    Sampling::Adaptive(n) => std::cmp::min(std::cmp::max(1, (n as f32 * prob.sqrt()).ceil() as u32), empty),
+  }
+}
+
+// Picks `samples` of the empty tiles of `board` for a chance node to spawn
+// on. The tiles are picked by a hash of the board, so a board is always
+// searched the same way. Since symmetric boards share hash table entries,
+// searches which sample start from the board the entry is stored under.
+fn sample_cells<B: GameBoard>(board: B, samples: u32) -> u32 {
+  let empty = board.empty_mask();
+  if samples >= empty.count_ones() {
+    return empty;
+  }
+
+  let bits = board.bits();
+  let seed = (bits as u64 ^ (bits >> 64) as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+  let mut rng = XorShift::new((seed >> 32) as u32);
+  let mut rest = empty;
+  let mut cells = 0;
+  for _ in 0..samples {
+    // Clear the tiles before the one picked.
+    let mut pick = rest;
+    for _ in 0..rng.gen_range(rest.count_ones()) {
+      pick &= pick - 1;
+    }
+    let cell = pick & pick.wrapping_neg();
+    cells |= cell;
+    rest &= !cell;
+  }
+  cells
+}
+
+// Picks moves for a game in progress. The death probability found for one
+// move decides how deep the next one is searched, so a Searcher should be
+// kept for the duration of a game. Results are kept in a transposition
+// table of `hash_mb` megabytes, which is shared by the threads searching each
+// move and kept from one move to the next. Positions at the end of the
+// search are scored by the Searcher's own evaluator, so Searchers with
+// different evaluators can play side by side.
+pub struct Searcher<E = Heuristic> {
+  pool: CpuPool,
+  state: PlayState,
+  rules: Arc<Ruleset>,
+  table: Arc<TransTable>,
+  evaluator: Arc<E>,
+  budget: Budget,
+  config: SearchConfig,
+  // Time spent searching so far this game.
+  game_time: Duration,
+}
+
+impl Searcher {
+  // A Searcher using the heuristic with the default weights.
+  pub fn new(rules: &Ruleset, hash_mb: usize) -> Searcher {
+    Searcher::with_evaluator(rules, hash_mb, Arc::new(Heuristic::new(HeuristicParams::default())))
+  }
+}
+
+impl<E> Searcher<E> {
+  // The evaluator can be shared by Searchers, so that tables it builds are
+  // only built once.
+  pub fn with_evaluator(rules: &Ruleset, hash_mb: usize, evaluator: Arc<E>) -> Searcher<E> {
+    Searcher::with_pool(CpuPool::new_num_cpus(), rules, hash_mb, evaluator)
+  }
+
+  // Like with_evaluator, but searches the moves of a position with
+  // `threads` threads rather than one per cpu.
+  pub fn with_threads(rules: &Ruleset, hash_mb: usize, evaluator: Arc<E>, threads: usize) -> Searcher<E> {
+    Searcher::with_pool(CpuPool::new(threads), rules, hash_mb, evaluator)
+  }
+
+  fn with_pool(pool: CpuPool, rules: &Ruleset, hash_mb: usize, evaluator: Arc<E>) -> Searcher<E> {
+    Searcher {
+      pool,
+      state: PlayState::ZeroProbDeath,
+      rules: Arc::new(rules.clone()),
+      table: Arc::new(TransTable::new(hash_mb)),
+      evaluator,
+      budget: Budget::default(),
+      config: SearchConfig::default(),
+      game_time: Duration::from_secs(0),
+    }
+  }
+
+  // Gets ready to play a new game, forgetting the results of the last one.
+  pub fn new_game(&mut self) {
+    self.state = PlayState::ZeroProbDeath;
+    self.game_time = Duration::from_secs(0);
+    self.table.clear();
+  }
+
+  pub fn set_budget(&mut self, budget: Budget) {
+    self.budget = budget;
+  }
+
+  pub fn set_config(&mut self, config: SearchConfig) -> Result<(), String> {
+    config.validate()?;
+    self.config = config;
+    Ok(())
+  }
+
+  pub fn search<B: GameBoard>(&mut self, real_board: B) -> SearchResult where E: Evaluator<B> {
+    let start = Instant::now();
+    // Search the canonical form of the board, so that symmetric positions
+    // are searched the same way, and translate the move back at the end.
+    let (board, transform) = real_board.canonical();
+    let mut bestdir = None;
+    let mut searched_depth = 0;
+    let mut bestexp = 0f32;
+    let mut best_end_prob = 1f32;
+
+    let mut searches = 0;
+    let mut stats = SearchStats::default();
+    self.table.new_search();
+    // Found for each search, since it depends on the type of board.
+    let upper_bound = self.evaluator.upper_bound();
+    let pruning = if upper_bound.is_some() { self.config.pruning } else { Pruning::None };
+
+    let game_left = self.budget.time_per_game.map(|time| time.checked_sub(self.game_time).unwrap_or_default());
+    let deadline = match (self.budget.time_per_move, game_left) {
+      (Some(a), Some(b)) => Some(start + std::cmp::min(a, b)),
+      (a, b) => a.or(b).map(|time| start + time),
+    };
+    let stopper = Arc::new(Stopper {
+      deadline,
+      max_nodes: self.budget.nodes_per_move,
+      nodes: AtomicU64::new(0),
+      stopped: AtomicBool::new(false),
+    });
+
+    loop {
+      let target = if self.budget.per_move() { self.config.max_depth } else { self.state.depth(board, &self.config) };
+      if target <= searched_depth || (searched_depth > 0 && bestdir.is_none()) {
+        break;
+      }
+      let depth = if self.budget.is_limited() { searched_depth + 1 } else { target };
+      let prob_cutoff = self.config.prob_cutoff;
+      let sampling = self.config.sampling;
+      // When pruning, each move is searched with the best score found so far
+      // for another move as alpha, since a move scoring no more than that
+      // won't be played. Scores are never negative, so their bits compare
+      // like they do.
+      let root_alpha = Arc::new(AtomicU32::new(0));
+
+      let res = futures::future::join_all(board.moves().map(|(dir, new_board)| {
+        let rules = self.rules.clone();
+        let table = self.table.clone();
+        let evaluator = self.evaluator.clone();
+        let stopper = if searched_depth > 0 { Some(stopper.clone()) } else { None };
+        let root_alpha = root_alpha.clone();
+        self.pool.spawn_fn(move || -> Result<(Direction, f32, f32, bool, SearchStats), ()> {
+          let mut ctx = SearchContext {
+            table: &table,
+            evaluator: &*evaluator,
+            rules: &rules,
+            prob_cutoff,
+            sampling,
+            stats: SearchStats::default(),
+            stopper: stopper.as_deref(),
+            unchecked_nodes: 0,
+            upper_bound: upper_bound.unwrap_or(0.0),
+            leaves: Vec::new(),
+            scores: Vec::new(),
+          };
+          // A move failing low only gets an upper bound for its score, and
+          // its end_prob is meaningless, so it can't be played.
+          let (exp, end_prob, exact) = if pruning == Pruning::None {
+            let (exp, end_prob) = ai_comp_move(new_board, depth, 1f32, &mut ctx);
+            (exp, end_prob, true)
+          } else {
+            let alpha = f32::from_bits(root_alpha.load(Ordering::Relaxed));
+            let (exp, end_prob) = star_comp_move(new_board, depth, 1f32, alpha, ctx.upper_bound, &mut ctx);
+            root_alpha.fetch_max(exp.max(0.0).to_bits(), Ordering::Relaxed);
+            (exp, end_prob, exp > alpha)
+          };
+          // Nodes short of a full batch haven't been added to the budget yet.
+          if let Some(stopper) = ctx.stopper {
+            stopper.add_nodes(ctx.unchecked_nodes);
+          }
+          Ok((dir, exp, end_prob, exact, ctx.stats))
+        })
+      })).wait().unwrap();
+
+      for &(_, _, _, _, dir_stats) in res.iter() {
+        stats.add(dir_stats);
+      }
+      // The moves of a search which was cut short can't be compared.
+      if stopper.is_stopped() {
+        break;
+      }
+
+      bestdir = None;
+      bestexp = 0.0;
+      best_end_prob = 1.0;
+      for &(dir, exp, end_prob, exact, _) in res.iter() {
+        if exact && exp > bestexp {
+          bestexp = exp;
+          bestdir = Some(dir);
+          best_end_prob = end_prob;
+        }
+      }
+
+      searched_depth = depth;
+      searches += 1;
+
+      // The formulas are for how deep to search, so only deep enough
+      // searches decide the state.
+      if depth == target || self.budget.per_move() {
+        self.state = PlayState::from_prob(best_end_prob, &self.config);
+      }
+
+      if searched_depth == 1 {
+        stopper.add_nodes(stats.nodes);
+      }
+      if stopper.is_stopped() {
+        break;
+      }
+    }
+    self.game_time += start.elapsed();
+
+    SearchResult {
+      dir: bestdir.map(|dir| transform.inverse().apply_dir(dir)),
+      exp: bestexp,
+      end_prob: best_end_prob,
+      depth: searched_depth,
+      searches,
+      state: self.state,
+      stats,
+    }
+  }
+}
+
+fn ai_comp_move<B: GameBoard, E: Evaluator<B>>(board: B, depth: u8, prob: f32, ctx: &mut SearchContext<B, E>) -> (f32, f32) {
+  if depth == 0 || prob < ctx.prob_cutoff {
+    return (ctx.evaluator.evaluate(board), 0f32);
+  }
+  ctx.stats.nodes += 1;
+  if ctx.stopped() {
+    return (0f32, 0f32);
+  }
+
+  // Symmetric boards have the same value, so they can share hash entries.
+  // Finding the canonical board costs more than it saves near the leaves.
+  let key = if depth > 1 { board.canonical().0 } else { board };
+  // Entries of boards whose chance nodes were sampled more sparsely than
+  // this one would be aren't good enough.
+  let samples = sample_count(key, prob, ctx.sampling) as u8;
+  if let Some(entry) = ctx.table.get(key) {
+    if entry.depth >= depth && entry.samples >= samples && entry.bound == Bound::Exact {
+      ctx.stats.hits += 1;
+      return (entry.score, entry.end_prob);
+    }
+  }
+
+  let board = if ctx.sampling == Sampling::Full { board } else { key };
+  let spawns = ctx.rules.spawns_per_turn;
+  let (score, end_prob) = if E::BATCHED && depth == 1 {
+    ai_spawn_batched(board, prob, ctx, spawns)
+  } else {
+    ai_spawn(board, depth, prob, ctx, spawns)
+  };
+
+  // Results of searches which were cut short are wrong.
+  if ctx.stopper.is_some_and(|stopper| stopper.is_stopped()) {
+    return (score, end_prob);
+  }
+  ctx.table.insert(key, Entry { depth, score, end_prob, bound: Bound::Exact, samples });
+
+  (score, end_prob)
+}
+
+// Chance node for placing one of the `spawns` tiles still to come this turn.
+// Boards between the spawns of a turn aren't hashed since they could collide
+// with boards from other parts of the turn.
+fn ai_spawn<B: GameBoard, E: Evaluator<B>>(board: B, depth: u8, prob: f32, ctx: &mut SearchContext<B, E>, spawns: u32) -> (f32, f32) {
+  if spawns == 0 || board.empty() == 0 {
+    return ai_player_move(board, depth, prob, ctx);
+  }
+
+  let mut score = 0f32;
+  let mut end_prob = 0f32;
+  for (new_board, spawn_prob) in ctx.spawn_outcomes(board, prob) {
+    let (move_score, move_end_prob) = ai_spawn(new_board, depth, prob * spawn_prob, ctx, spawns - 1);
+    score += move_score * spawn_prob;
+    end_prob += move_end_prob * spawn_prob;
+  }
+
+  (score, end_prob)
+}
+
+fn ai_player_move<B: GameBoard, E: Evaluator<B>>(board: B, depth: u8, prob: f32, ctx: &mut SearchContext<B, E>) -> (f32, f32) {
+  let mut score = 0f32;
+  let mut end_prob = 1f32;
+
+  for (_, new_board) in board.moves() {
+    let (move_score, move_end_prob) = ai_comp_move(new_board, depth - 1, prob, ctx);
+    if move_score > score {
+      score = move_score;
+      end_prob = move_end_prob;
+    }
+  }
+
+  (score, end_prob)
+}
+
+// The bounded versions of ai_comp_move, ai_spawn and ai_player_move, used
+// when pruning. The score of a position is only found if it lies between
+// alpha and beta. Otherwise the search of a chance node may stop early, and
+// return a score of at most alpha or at least beta, which the score of the
+// position is known to be beyond. Player nodes score at least 0, since a lost
+// position does, and at most the upper bound of the evaluator.
+fn star_comp_move<B: GameBoard, E: Evaluator<B>>(board: B, depth: u8, prob: f32, alpha: f32, beta: f32,
+                                                 ctx: &mut SearchContext<B, E>) -> (f32, f32) {
+  if depth == 0 || prob < ctx.prob_cutoff {
+    return (ctx.evaluator.evaluate(board), 0f32);
+  }
+  ctx.stats.nodes += 1;
+  if ctx.stopped() {
+    return (0f32, 0f32);
+  }
+
+  let key = if depth > 1 { board.canonical().0 } else { board };
+  let samples = sample_count(key, prob, ctx.sampling) as u8;
+  if let Some(entry) = ctx.table.get(key) {
+    let usable = match entry.bound {
+      Bound::Exact => true,
+      Bound::Lower => entry.score >= beta,
+      Bound::Upper => entry.score <= alpha,
+    };
+    if entry.depth >= depth && entry.samples >= samples && usable {
+      ctx.stats.hits += 1;
+      return (entry.score, entry.end_prob);
+    }
+  }
+
+  let board = if ctx.sampling == Sampling::Full { board } else { key };
+  let spawns = ctx.rules.spawns_per_turn;
+  let (score, end_prob) = star_spawn(board, depth, prob, alpha, beta, ctx, spawns);
+
+  if ctx.stopper.is_some_and(|stopper| stopper.is_stopped()) {
+    return (score, end_prob);
+  }
+  let bound = if score <= alpha {
+    Bound::Upper
+  } else if score >= beta {
+    Bound::Lower
+  } else {
+    Bound::Exact
+  };
+  ctx.table.insert(key, Entry { depth, score, end_prob, bound, samples });
+
+  (score, end_prob)
+}
+
+fn star_spawn<B: GameBoard, E: Evaluator<B>>(board: B, depth: u8, prob: f32, alpha: f32, beta: f32,
+                                             ctx: &mut SearchContext<B, E>, spawns: u32) -> (f32, f32) {
+  if spawns == 0 || board.empty() == 0 {
+    return star_player_move(board, depth, prob, alpha, beta, ctx);
+  }
+  let upper = ctx.upper_bound;
+
+  // Stops as soon as the outcomes searched so far, together with the bounds
+  // of the rest, put the score outside of alpha to beta. Outcomes not yet
+  // searched score at least 0.
+  let mut score = 0f32;
+  let mut end_prob = 0f32;
+  let mut remaining = 1f32;
+  for (new_board, spawn_prob) in ctx.spawn_outcomes(board, prob) {
+    remaining = (remaining - spawn_prob).max(0.0);
+    let child_alpha = ((alpha - score - remaining * upper) / spawn_prob).max(0.0);
+    let child_beta = ((beta - score) / spawn_prob).min(upper);
+    let (move_score, move_end_prob) = star_spawn(new_board, depth, prob * spawn_prob, child_alpha, child_beta, ctx,
+                                                 spawns - 1);
+    score += move_score * spawn_prob;
+    end_prob += move_end_prob * spawn_prob;
+    if score + remaining * upper <= alpha {
+      return (score + remaining * upper, end_prob);
+    }
+    if score >= beta {
+      return (score, end_prob);
+    }
+  }
+
+  (score, end_prob)
+}
+
+fn star_player_move<B: GameBoard, E: Evaluator<B>>(board: B, depth: u8, prob: f32, alpha: f32, beta: f32,
+                                                   ctx: &mut SearchContext<B, E>) -> (f32, f32) {
+  let mut score = 0f32;
+  let mut end_prob = 1f32;
+
+  for (_, new_board) in board.moves() {
+    let (move_score, move_end_prob) = star_comp_move(new_board, depth - 1, prob, alpha.max(score), beta, ctx);
+    if move_score > score {
+      score = move_score;
+      end_prob = move_end_prob;
+      if score >= beta {
+        break;
+      }
+    }
+  }
+
+  (score, end_prob)
+}
+
+// Gives the same result as ai_spawn at depth 1, where every board after the
+// next move is a leaf, but scores all of those boards in one batch.
+fn ai_spawn_batched<B: GameBoard, E: Evaluator<B>>(board: B, prob: f32, ctx: &mut SearchContext<B, E>, spawns: u32)
+                                                   -> (f32, f32) {
+  ctx.leaves.clear();
+  gather_leaves(board, prob, ctx, spawns);
+  ctx.scores.resize(ctx.leaves.len(), 0.0);
+  ctx.evaluator.evaluate_batch(&ctx.leaves, &mut ctx.scores);
+  combine_leaves(board, prob, ctx, spawns, &mut 0)
+}
+
+fn gather_leaves<B: GameBoard, E: Evaluator<B>>(board: B, prob: f32, ctx: &mut SearchContext<B, E>, spawns: u32) {
+  if spawns == 0 || board.empty() == 0 {
+    ctx.leaves.extend(board.moves().map(|(_, new_board)| new_board));
+    return;
+  }
+  for (new_board, spawn_prob) in ctx.spawn_outcomes(board, prob) {
+    gather_leaves(new_board, prob * spawn_prob, ctx, spawns - 1);
+  }
+}
+
+// Goes through the chance nodes in the same order as gather_leaves, with
+// `next` the index of the score of the next leaf.
+fn combine_leaves<B: GameBoard, E: Evaluator<B>>(board: B, prob: f32, ctx: &SearchContext<B, E>, spawns: u32,
+                                                 next: &mut usize) -> (f32, f32) {
+  if spawns == 0 || board.empty() == 0 {
+    let mut score = 0f32;
+    let mut end_prob = 1f32;
+    for _ in board.moves() {
+      if ctx.scores[*next] > score {
+        score = ctx.scores[*next];
+        end_prob = 0.0;
+      }
+      *next += 1;
+    }
+    return (score, end_prob);
+  }
+
+  let mut score = 0f32;
+  let mut end_prob = 0f32;
+  for (new_board, spawn_prob) in ctx.spawn_outcomes(board, prob) {
+    let (move_score, move_end_prob) = combine_leaves(new_board, prob * spawn_prob, ctx, spawns - 1, next);
+    score += move_score * spawn_prob;
+    end_prob += move_end_prob * spawn_prob;
+  }
+  (score, end_prob)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use board::Board;
+
+  #[test]
+  fn budget() {
+    let board = Board(0x1234_0000_0100_0001);
+    let mut searcher = Searcher::new(&Ruleset::default(), 1);
+    searcher.set_budget(Budget { nodes_per_move: Some(2000), ..Budget::default() });
+    let res = searcher.search(board);
+    assert!(res.dir.is_some());
+    assert!(res.depth >= 1 && res.depth < SearchConfig::default().max_depth);
+    assert_eq!(res.searches, res.depth as u32);
+
+    // The first depth is finished even when there is no time at all.
+    let mut searcher = Searcher::new(&Ruleset::default(), 1);
+    searcher.set_budget(Budget { time_per_game: Some(Duration::from_secs(0)), ..Budget::default() });
+    let res = searcher.search(board);
+    assert_eq!(res.depth, 1);
+    assert!(res.dir.is_some());
+  }
+
+  // Scores every position the same.
+  struct Constant;
+
+  impl<B: GameBoard> Evaluator<B> for Constant {
+    fn evaluate(&self, _: B) -> f32 {
+      1.0
+    }
+  }
+
+  #[test]
+  fn evaluator() {
+    let board = Board(0x1234_0000_0100_0001);
+    let mut searcher = Searcher::with_evaluator(&Ruleset::default(), 1, Arc::new(Constant));
+    let res = searcher.search(board);
+    assert!(res.dir.is_some());
+    assert!((res.exp - 1.0).abs() < 1e-4);
+    assert!(Searcher::new(&Ruleset::default(), 1).search(board).exp > 1000.0);
+  }
+
+  // The heuristic, scored in batches.
+  struct Batched(Heuristic);
+
+  impl<B: GameBoard> Evaluator<B> for Batched {
+    const BATCHED: bool = true;
+
+    fn evaluate(&self, board: B) -> f32 {
+      self.0.score(board)
+    }
+  }
+
+  #[test]
+  fn batched() {
+    for spawns_per_turn in 1..3 {
+      let rules = Ruleset::new(Ruleset::default().spawns().to_vec(), spawns_per_turn, 2).unwrap();
+      for board in [Board(0x1234_0000_0100_0001), Board(0x1234_5678_9abc_0001)].iter() {
+        let heuristic = Arc::new(Batched(Heuristic::new(HeuristicParams::default())));
+        let res = Searcher::with_evaluator(&rules, 1, heuristic).search(*board);
+        let expected = Searcher::new(&rules, 1).search(*board);
+        // Threads searching different moves share the hash table, so results
+        // may differ slightly when they run at the same time.
+        assert_eq!(res.dir, expected.dir);
+        assert!((res.exp - expected.exp).abs() <= 1e-4 * expected.exp);
+      }
+    }
+  }
+
+  #[test]
+  fn pruning() {
+    let board = Board(0x0012_0123_1235_2349);
+    let rules = Ruleset::default();
+    let search = |pruning| {
+      let mut searcher = Searcher::new(&rules, 16);
+      searcher.set_config(SearchConfig { pruning, ..SearchConfig::default() }).unwrap();
+      searcher.search(board)
+    };
+    let expected = search(Pruning::None);
+    let res = search(Pruning::Star1);
+    assert_eq!(res.dir, expected.dir);
+    assert!((res.exp - expected.exp).abs() <= 1e-4 * expected.exp);
+    assert!(res.stats.nodes < expected.stats.nodes);
+
+    // Without an upper bound to prune with, the search is the plain one.
+    let mut searcher = Searcher::with_evaluator(&rules, 16, Arc::new(Constant));
+    searcher.set_config(SearchConfig { pruning: Pruning::Star1, ..SearchConfig::default() }).unwrap();
+    assert!((searcher.search(board).exp - 1.0).abs() < 1e-4);
+  }
+
+  #[test]
+  fn sampling() {
+    let board = Board(0x1234_0000_0100_0001);
+    let rules = Ruleset::default();
+    let empty = board.empty_mask();
+    assert_eq!(sample_count(board, 1.0, Sampling::Full), 10);
+    assert_eq!(sample_count(board, 1.0, Sampling::Fixed(16)), 10);
+    assert_eq!(sample_count(board, 1.0, Sampling::Adaptive(8)), 8);
+    assert_eq!(sample_count(board, 0.01, Sampling::Adaptive(8)), 1);
+    assert_eq!(sample_cells(board, 10), empty);
+    let cells = sample_cells(board, 3);
+    assert_eq!(cells.count_ones(), 3);
+    assert_eq!(cells & !empty, 0);
+    assert_eq!(sample_cells(board, 3), cells);
+    // The sampled tiles stand for all of them.
+    let total: f32 = board.spawn_outcomes_in(&rules, cells).map(|(_, prob)| prob).sum();
+    assert!((total - 1.0).abs() < 1e-6);
+
+    let search = |sampling| {
+      let mut searcher = Searcher::new(&rules, 16);
+      searcher.set_config(SearchConfig { sampling, ..SearchConfig::preset("fast").unwrap() }).unwrap();
+      searcher.search(board)
+    };
+    let expected = search(Sampling::Full);
+    let res = search(Sampling::Fixed(16));
+    assert_eq!(res.dir, expected.dir);
+    assert!((res.exp - expected.exp).abs() <= 1e-4 * expected.exp);
+    let res = search(Sampling::Adaptive(4));
+    assert!(res.dir.is_some() && res.stats.nodes < expected.stats.nodes);
+  }
+}
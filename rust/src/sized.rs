@@ -0,0 +1,124 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use board::GameBoard;
+use direction::Direction;
+use notation;
+use rows::RowTables;
+
+// A board of N by N tiles, for N from 2 to 5, used to play variants such as
+// 3x3 and 5x5. Tiles take 4 bits like in Board, so ranks stop at 32768.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SizedBoard<const N: i32>(pub u128);
+
+static TABLES: [OnceLock<RowTables>; 6] = [OnceLock::new(), OnceLock::new(), OnceLock::new(),
+                                           OnceLock::new(), OnceLock::new(), OnceLock::new()];
+
+impl<const N: i32> SizedBoard<N> {
+  fn tables() -> &'static RowTables {
+    assert!(N >= 2 && N <= 5, "unsupported board size {}", N);
+    TABLES[N as usize].get_or_init(|| RowTables::new(N, 4, 15))
+  }
+
+  fn slide_rows(self, left: bool) -> (SizedBoard<N>, u32) {
+    let (res, reward) = SizedBoard::<N>::tables().slide_rows(self.0, left);
+    (SizedBoard(res), reward)
+  }
+}
+
+impl<const N: i32> fmt::Display for SizedBoard<N> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    notation::format(*self, f)
+  }
+}
+
+impl<const N: i32> FromStr for SizedBoard<N> {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<SizedBoard<N>, String> {
+    notation::parse(s)
+  }
+}
+
+impl<const N: i32> GameBoard for SizedBoard<N> {
+  const WIDTH: i32 = N;
+  const TILE_BITS: u32 = 4;
+  const MAX_RANK: i32 = 15;
+
+  fn from_bits(bits: u128) -> SizedBoard<N> {
+    SizedBoard(bits)
+  }
+
+  fn bits(self) -> u128 {
+    self.0
+  }
+
+  fn get_tile(self, tile: i32) -> i32 {
+    debug_assert!((0..N * N).contains(&tile));
+    ((self.0 >> (tile * 4)) & 0xf) as i32
+  }
+
+  fn set_tile(self, tile: i32, val: i32) -> SizedBoard<N> {
+    debug_assert_eq!(self.get_tile(tile), 0);
+    SizedBoard(self.0 | (val as u128) << (tile * 4))
+  }
+
+  fn slide(self, dir: Direction) -> SizedBoard<N> {
+    self.slide_with_reward(dir).0
+  }
+
+  fn slide_with_reward(self, dir: Direction) -> (SizedBoard<N>, u32) {
+    match dir {
+      Direction::Right => self.slide_rows(false),
+      Direction::Down => {
+        let (board, reward) = self.transpose().slide_rows(false);
+        (board.transpose(), reward)
+      }
+      Direction::Left => self.slide_rows(true),
+      Direction::Up => {
+        let (board, reward) = self.transpose().slide_rows(true);
+        (board.transpose(), reward)
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use board::Board;
+
+  #[test]
+  fn small() {
+    // Ranks, with the top row first:
+    // 0 1 2
+    // 1 1 0
+    // 3 0 3
+    let board = SizedBoard::<3>(0x012_110_303);
+    assert_eq!(board.slide_with_reward(Direction::Right), (SizedBoard(0x012_002_004), 4 + 16));
+    assert_eq!(board.slide_with_reward(Direction::Left), (SizedBoard(0x120_200_400), 4 + 16));
+    assert_eq!(board.slide_with_reward(Direction::Down), (SizedBoard(0x000_102_323), 4));
+    assert_eq!(board.slide_with_reward(Direction::Up), (SizedBoard(0x122_303_000), 4));
+    assert_eq!(board.transpose(), SizedBoard(0x013_110_203));
+    assert_eq!(board.empty(), 3);
+    assert_eq!(board.distinct(), 3);
+    assert_eq!(board.max_val(), 3);
+  }
+
+  #[test]
+  fn matches_board() {
+    let boards = [0x0001_0001_0002_0003u64, 0x0001_0020_0300_1001, 0x5550_0333_aa0a_c0cc,
+                  0x1110_0222_bb0b_e0ee, 0x530c_50ac_53a0_03ac, 0x02be_10be_12b0_120e,
+                  0x1234_5678_9abc_def0, 0x100a_02b0_0c30_d004, 0xff00_f0f0_0ff1_ff0f];
+    for b in boards.iter() {
+      let board = Board(*b);
+      let sized = SizedBoard::<4>(*b as u128);
+      for dir in Direction::ALL.iter().cloned() {
+        let (slid, reward) = board.slide_with_reward(dir);
+        assert_eq!(sized.slide_with_reward(dir), (SizedBoard(slid.0 as u128), reward));
+      }
+      assert_eq!(sized.transpose(), SizedBoard(board.transpose().0 as u128));
+      assert_eq!(sized.heur_score(), board.heur_score());
+    }
+  }
+}
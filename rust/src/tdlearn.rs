@@ -1,15 +1,20 @@
-mod board;
-mod movavg;
-
+extern crate p2048;
 extern crate byteorder;
-
-use board::Board;
-use movavg::MovAvg;
+extern crate getopts;
+
+use p2048::board::Board;
+use p2048::game::Game;
+use p2048::movavg::MovAvg;
+use p2048::ntuple::{NTuple, Tuples, VPos, MAX_TUPLES};
+use p2048::render::{self, Frame, Renderer};
+use p2048::rng::{self, Rng, XorShift};
+use p2048::rules::{self, Ruleset};
+use p2048::sized::SizedBoard;
 use byteorder::{LittleEndian, WriteBytesExt};
+use getopts::Options;
 use std::fs::File;
-use std::io::BufWriter;
+use std::io::{self, BufWriter};
 
-const N_V_TABLES: usize = 17;
 const ALPHA_START: f32 = 0.0025;
 const ALPHA_DECREASE: f32 = 5.0;
 const ALPHA_RATE: f32 = 300000.0;
@@ -18,66 +23,89 @@ const EXPLORE_DECREASE_FACTOR: f32 = 1.0;
 const START_RECORDING_SCORE: i32 = 0; // 40_000
 const RECORD_N_MOVES: u32 = 10_000_000;
 
-static mut V_TABLES : [[f32; 65536]; N_V_TABLES] = [[0f32; 65536]; N_V_TABLES];
-
-type VPos = [u16; N_V_TABLES];
-
-impl Board {
-  fn vpos(self) -> VPos {
-    let mut res : VPos = [0u16; N_V_TABLES];
-    // First the horizontal positions
-    for i in 0..4 {
-      res[i] = ((self.0 >> (16 * i)) & 0xffff) as u16;
+fn main() {
+  let args: Vec<String> = std::env::args().collect();
+
+  let mut opts = Options::new();
+  opts.optflag("h", "help", "Print this message.");
+  opts.optopt("", "seed", "Seed for tile spawns and exploration. Defaults to a time based seed.", "number");
+  opts.optopt("", "size", "Width and height of the board, from 3 to 5. Defaults to 4.", "number");
+  opts.optopt("", "spawns", "Tiles placed by the computer with their relative weights. Defaults to 2:9,4:1.", "value:weight,...");
+  opts.optopt("", "spawns-per-turn", "Number of tiles placed after each move. Defaults to 1.", "number");
+  opts.optopt("", "start-tiles", "Number of tiles on the board at the start of a game. Defaults to 2.", "number");
+  opts.optopt("", "games", "Number of games to train for. Defaults to training until stopped.", "number");
+  opts.optopt("", "load", "Tables saved by --save to continue training from. The learning rate starts over.", "FILE");
+  opts.optopt("", "save", "File to save the tables in every 2000 games and at the end, for use with expmax --eval ntuple.", "FILE");
+  opts.optopt("", "render", &format!("How to draw the progress boards: {}. Defaults to redraw when writing to a terminal and plain otherwise.", render::NAMES), "NAME");
+
+  let options_str = opts.usage(&format!("Usage: {} [options]", args[0]));
+  let matches = match opts.parse(&args[1..]) {
+    Ok(m) => { m }
+    Err(e) => {
+      println!("{}\n{}", e, options_str);
+      return;
     }
+  };
+  if matches.opt_present("h") {
+    println!("{}", options_str);
+    return;
+  }
 
-    // Then vertical
-    let t = self.transpose();
-    for i in 0..4 {
-      res[i+4] = ((t.0 >> (16 * i)) & 0xffff) as u16;
+  let seed = matches.opt_str("seed").map_or_else(rng::seed_from_time, |seed_str|
+    seed_str.parse::<u32>().unwrap()
+  );
+  println!("Seed: {}", seed);
+
+  let default_rules = Ruleset::default();
+  let spawns = matches.opt_str("spawns").map_or(Ok(default_rules.spawns().to_vec()), |spec| rules::parse_spawns(&spec));
+  let spawns_per_turn = matches.opt_str("spawns-per-turn").map_or(default_rules.spawns_per_turn, |num_str|
+    num_str.parse::<u32>().unwrap()
+  );
+  let start_tiles = matches.opt_str("start-tiles").map_or(default_rules.start_tiles, |num_str|
+    num_str.parse::<u32>().unwrap()
+  );
+  let rules = match spawns.and_then(|spawns| Ruleset::new(spawns, spawns_per_turn, start_tiles)) {
+    Ok(rules) => rules,
+    Err(e) => {
+      println!("{}\n{}", e, options_str);
+      return;
     }
+  };
 
-    // Then squares
-    let mut b1 = self.0;
-    let mut b2 = self.0 >> 8;
-    let mut n = 8;
-    for _ in 0..3 {
-      for _ in 0..3 {
-        res[n] = ((b1 & 0xff) | (b2 & 0xff00)) as u16;
-        n += 1;
-        b1 >>= 4;
-        b2 >>= 4;
-      }
-      b1 >>= 4;
-      b2 >>= 4;
+  let mut renderer = match matches.opt_str("render").map_or_else(|| Ok(render::default_renderer()), |name| render::from_name(&name)) {
+    Ok(renderer) => renderer,
+    Err(e) => {
+      println!("{}\n{}", e, options_str);
+      return;
     }
-
-    res
-  }
-}
-
-#[cfg(not(feature = "best-symmetry"))]
-fn get_val(board: Board) -> (VPos, f32) {
-  let vpos = board.vpos();
-  (vpos, vpos.iter().zip(unsafe { V_TABLES.iter() }).map(|(pos, table)| unsafe { table.get_unchecked(*pos as usize) }).sum())
-}
-
-#[cfg(feature = "best-symmetry")]
-fn get_val(board: Board) -> (VPos, f32) {
-  let mut bestvpos = [0; N_V_TABLES];
-  let mut bestval = std::f32::NEG_INFINITY;
-  for symm in board.symmetries() {
-    let vpos = symm.vpos();
-    let val = vpos.iter().zip(unsafe { V_TABLES.iter() }).map(|(pos, table)| unsafe { table.get_unchecked(*pos as usize) }).sum();
-    if val > bestval {
-      bestval = val;
-      bestvpos = vpos;
+  };
+
+  let games = matches.opt_str("games").map(|num_str| num_str.parse::<u32>().unwrap());
+  let load = matches.opt_str("load");
+  let save = matches.opt_str("save");
+
+  let res = match matches.opt_str("size").map_or(4, |size_str| size_str.parse::<i32>().unwrap()) {
+    3 => train::<SizedBoard<3>>(seed, &rules, renderer.as_mut(), games, load.as_ref(), save.as_ref()),
+    4 => train::<Board>(seed, &rules, renderer.as_mut(), games, load.as_ref(), save.as_ref()),
+    5 => train::<SizedBoard<5>>(seed, &rules, renderer.as_mut(), games, load.as_ref(), save.as_ref()),
+    size => {
+      println!("Unsupported board size: {}\n{}", size, options_str);
+      Ok(())
     }
+  };
+  if let Err(e) = res {
+    println!("{}", e);
   }
-  (bestvpos, bestval)
 }
 
-fn main() {
-  board::init();
+fn train<B: Tuples>(seed: u32, rules: &Ruleset, renderer: &mut dyn Renderer, games: Option<u32>,
+                    load: Option<&String>, save: Option<&String>) -> io::Result<()> {
+  let mut rng = XorShift::new(seed);
+
+  let mut ntuple = match load {
+    Some(filename) => NTuple::<B>::load(filename)?,
+    None => NTuple::<B>::new(),
+  };
   let mut n_games: u32 = 0;
 
   let mut avg_score = MovAvg::new();
@@ -87,7 +115,9 @@ fn main() {
   let mut record_file = None;
 
   loop {
-    if START_RECORDING_SCORE != 0 && record_file.is_none() && avg_score.avg() > START_RECORDING_SCORE {
+    // The recorded boards are read as 4x4 boards.
+    if START_RECORDING_SCORE != 0 && B::WIDTH == 4 && B::TILE_BITS == 4 &&
+       record_file.is_none() && avg_score.avg() > START_RECORDING_SCORE {
       let mut file = BufWriter::new(File::create("2048training").unwrap());
       file.write_u32::<LittleEndian>(RECORD_N_MOVES).unwrap();
       record_file = Some(file);
@@ -95,109 +125,96 @@ fn main() {
 
     let alpha = ALPHA_START / ALPHA_DECREASE.powf((n_games as f32) / ALPHA_RATE);
     n_games += 1;
-    let mut board = Board(0);
-    board.comp_move();
-    let (mut prev_vpos, mut prev_val) = get_val(board);
+    let mut game = Game::<B>::new(rules, rng);
+    // The value of the position after the previous move, which is learned
+    // from the value of the next one.
+    let mut prev: Option<(VPos, f32)> = None;
     let explore = std::cmp::max(1, (n_games as f32 * EXPLORE_DECREASE_FACTOR) as u32);
 
     loop {
-      board.comp_move();
+      let board = game.board();
 
-      let mut bestdir = -1;
-      let mut bestvpos = [0; N_V_TABLES];
-      let mut bestboard = Board(0);
+      let mut bestdir = None;
+      let mut bestvpos = [0; MAX_TUPLES];
+      let mut bestreward = 0;
       let mut bestval = std::f32::NEG_INFINITY;
 
-      let rand_move = rng(explore) == 0;
+      let legal_moves = board.legal_moves();
+      let rand_move = game.rng().gen_range(explore) == 0;
       if rand_move {
-        let mut ndir = 0;
-        let mut allowed_dirs = [0; 4];
-
-        for dir in 0..4 {
-          let newboard = board.slide(dir);
-          if newboard != board {
-            allowed_dirs[ndir] = dir;
-            ndir += 1;
-          }
-        }
-
-        if ndir > 0 {
-          bestdir = allowed_dirs[rng(ndir as u32) as usize];
-          bestboard = board.slide(bestdir);
-          let (vpos, val) = get_val(bestboard);
+        if !legal_moves.is_empty() {
+          let dir = legal_moves.iter().nth(game.rng().gen_range(legal_moves.len() as u32) as usize).unwrap();
+          let (newboard, reward) = board.slide_with_reward(dir);
+          bestdir = Some(dir);
+          bestreward = reward;
+          let (vpos, val) = ntuple.get_val(newboard);
           bestvpos = vpos;
           bestval = val;
         }
 
       } else {
-        for dir in 0..4 {
-          let newboard = board.slide(dir);
-          if newboard == board {
-            continue;
-          }
-
-          // Optimizing out adding 'r' since it's 1 for every direction.
-          let (vpos, val) = get_val(newboard);
-          if val > bestval {
+        for dir in legal_moves.iter() {
+          let (newboard, reward) = board.slide_with_reward(dir);
+          let (vpos, val) = ntuple.get_val(newboard);
+          if bestdir.is_none() || reward as f32 + val > bestreward as f32 + bestval {
             bestval = val;
             bestvpos = vpos;
-            bestdir = dir;
-            bestboard = newboard;
+            bestdir = Some(dir);
+            bestreward = reward;
           }
         }
       }
 
       // Learn
       if !rand_move {
-        let exp_value = if bestdir == -1 {
+        let exp_value = if bestdir.is_none() {
                           0.0
                         }
                         else {
-                          1.0 + bestval
+                          bestreward as f32 + bestval
                         };
         if let Some(ref mut file) = record_file {
-          file.write_u64::<LittleEndian>(board.0).unwrap();
+          file.write_u64::<LittleEndian>(board.bits() as u64).unwrap();
           file.write_f32::<LittleEndian>(exp_value).unwrap();
           n_record -= 1;
           if n_record == 0 {
-            return;
+            return Ok(());
           }
         }
 
-        let adjust = (exp_value - prev_val) * alpha;
-        prev_vpos.iter().zip(unsafe { V_TABLES.iter_mut() })
-                        .for_each(|(pos, table)| unsafe {
-                          *table.get_unchecked_mut(*pos as usize) += adjust;
-                        });
+        if let Some((prev_vpos, prev_val)) = prev {
+          let adjust = (exp_value - prev_val) * alpha;
+          ntuple.adjust(&prev_vpos, adjust);
+        }
       }
 
       // Dead
-      if bestdir == -1 {
-        break;
-      }
+      let dir = match bestdir {
+        Some(dir) => dir,
+        None => break,
+      };
 
       // Execute best move
-      prev_vpos = bestvpos;
-      prev_val = bestval;
-      board = bestboard;
+      prev = Some((bestvpos, bestval));
+      game.play(dir);
     }
 
-    avg_score.add(board.game_score(0));
+    rng = *game.rng();
+    let score = game.score();
+
+    avg_score.add(score as i32);
     avg_score.drop();
 
-    if (n_games % 2000) == 0 {
-      board.print(0, true,
-                  &format!("Avg score: {}  \nNum games: {}\n", avg_score.avg(), n_games));
+    let done = games == Some(n_games);
+    if n_games.is_multiple_of(2000) || done {
+      renderer.show(&Frame::new(game.board(), score,
+                                &format!("Avg score: {}\nNum games: {}\n", avg_score.avg(), n_games))).unwrap();
+      if let Some(filename) = save {
+        ntuple.save(filename)?;
+      }
+    }
+    if done {
+      return Ok(());
     }
   }
 }
-
-static mut SEED: u32 = 0x17014711;
-fn rng(max: u32) -> u32 {
-  let mut x = unsafe { SEED };
-  x ^= x << 13;
-  x ^= x >> 17;
-  x ^= x << 5;
-  unsafe { SEED = x; }
-  (x % max) as u32
-}
@@ -0,0 +1,213 @@
+use std;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use board::GameBoard;
+
+// Whether the score of an entry is the board's score or only a bound on it,
+// as found by a search which was pruned.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Bound {
+  Exact,
+  Lower,
+  Upper,
+}
+
+// What the search found for a board.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Entry {
+  pub depth: u8,
+  pub score: f32,
+  pub end_prob: f32,
+  pub bound: Bound,
+  // How many empty tiles the chance node after the board spawned on, which
+  // is fewer than all of them when chance nodes are sampled.
+  pub samples: u8,
+}
+
+const BUCKET_SLOTS: usize = 4;
+
+// The slots of a bucket fill a cache line. Each slot is a check word and a
+// data word. The check word holds the top 49 bits of the board's hash, the
+// samples, the kind of bound, the generation it was stored in and the depth,
+// and is stored xor'ed with the data word so that a slot torn by two threads
+// writing it at once doesn't match any board.
+#[repr(align(64))]
+#[derive(Default)]
+struct Bucket {
+  slots: [[AtomicU64; 2]; BUCKET_SLOTS],
+}
+
+const DEPTH_MASK: u64 = 0x1f;
+const GENERATION_SHIFT: u32 = 5;
+const GENERATION_MASK: u8 = 0x7;
+const BOUND_SHIFT: u32 = 8;
+const SAMPLES_SHIFT: u32 = 10;
+// Room for every tile of the largest boards.
+const SAMPLES_MASK: u8 = 0x1f;
+const HASH_MASK: u64 = !0x7fff;
+
+const BOUNDS: [Bound; 3] = [Bound::Exact, Bound::Lower, Bound::Upper];
+
+// A fixed size hash table of search results, which all search threads share
+// without locking. When a bucket is full, results from earlier searches are
+// replaced first, and then the ones searched least deep.
+pub struct TransTable {
+  buckets: Vec<Bucket>,
+  mask: u64,
+  generation: AtomicU8,
+}
+
+fn hash<B: GameBoard>(board: B) -> u64 {
+  let bits = board.bits();
+  let mut h = (bits as u64) ^ ((bits >> 64) as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+  h = h.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+  h ^ (h >> 32)
+}
+
+impl TransTable {
+  // Uses at most `megabytes` of memory, rounded down to a power of two
+  // number of buckets.
+  pub fn new(megabytes: usize) -> TransTable {
+    let wanted = std::cmp::max(1, (megabytes << 20) / std::mem::size_of::<Bucket>());
+    let n_buckets = 1 << (usize::BITS - 1 - wanted.leading_zeros());
+    TransTable {
+      buckets: (0..n_buckets).map(|_| Bucket::default()).collect(),
+      mask: n_buckets as u64 - 1,
+      generation: AtomicU8::new(0),
+    }
+  }
+
+  pub fn size_bytes(&self) -> usize {
+    self.buckets.len() * std::mem::size_of::<Bucket>()
+  }
+
+  // Marks the results stored so far as being from an earlier search, so that
+  // they are the first to be replaced.
+  pub fn new_search(&self) {
+    self.generation.fetch_add(1, Ordering::Relaxed);
+  }
+
+  // Forgets every result stored, leaving the table as it was when new.
+  pub fn clear(&self) {
+    self.generation.store(0, Ordering::Relaxed);
+    for bucket in self.buckets.iter() {
+      for slot in bucket.slots.iter() {
+        slot[0].store(0, Ordering::Relaxed);
+        slot[1].store(0, Ordering::Relaxed);
+      }
+    }
+  }
+
+  pub fn get<B: GameBoard>(&self, board: B) -> Option<Entry> {
+    let h = hash(board);
+    let bucket = &self.buckets[(h & self.mask) as usize];
+    for slot in bucket.slots.iter() {
+      let data = slot[1].load(Ordering::Relaxed);
+      let check = slot[0].load(Ordering::Relaxed) ^ data;
+      if check & HASH_MASK == h & HASH_MASK && check & DEPTH_MASK != 0 {
+        return Some(Entry {
+          depth: (check & DEPTH_MASK) as u8,
+          score: f32::from_bits(data as u32),
+          end_prob: f32::from_bits((data >> 32) as u32),
+          bound: BOUNDS[(check >> BOUND_SHIFT) as usize & 3],
+          samples: (check >> SAMPLES_SHIFT) as u8 & SAMPLES_MASK,
+        });
+      }
+    }
+    None
+  }
+
+  pub fn insert<B: GameBoard>(&self, board: B, entry: Entry) {
+    debug_assert!(entry.depth != 0 && entry.depth as u64 <= DEPTH_MASK && entry.samples <= SAMPLES_MASK);
+    let h = hash(board);
+    let generation = self.generation.load(Ordering::Relaxed) & GENERATION_MASK;
+    let bucket = &self.buckets[(h & self.mask) as usize];
+
+    // The slot already holding the board, or else the one least worth
+    // keeping.
+    let mut victim = 0;
+    let mut victim_rank = u64::MAX;
+    for (n, slot) in bucket.slots.iter().enumerate() {
+      let check = slot[0].load(Ordering::Relaxed) ^ slot[1].load(Ordering::Relaxed);
+      let depth = check & DEPTH_MASK;
+      if check & HASH_MASK == h & HASH_MASK && depth != 0 {
+        let exact = (check >> BOUND_SHIFT) & 3 == 0;
+        if depth > entry.depth as u64 || (depth == entry.depth as u64 && exact && entry.bound != Bound::Exact) {
+          return;
+        }
+        victim = n;
+        break;
+      }
+      let current = ((check >> GENERATION_SHIFT) as u8 & GENERATION_MASK) == generation;
+      let rank = (current as u64) << 8 | depth;
+      if rank < victim_rank {
+        victim = n;
+        victim_rank = rank;
+      }
+    }
+
+    let data = entry.score.to_bits() as u64 | (entry.end_prob.to_bits() as u64) << 32;
+    let bound = BOUNDS.iter().position(|bound| *bound == entry.bound).unwrap() as u64;
+    let check = (h & HASH_MASK) | (entry.samples as u64) << SAMPLES_SHIFT | bound << BOUND_SHIFT |
+                (generation as u64) << GENERATION_SHIFT | entry.depth as u64;
+    let slot = &bucket.slots[victim];
+    slot[0].store(check ^ data, Ordering::Relaxed);
+    slot[1].store(data, Ordering::Relaxed);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use board::Board;
+  use wide::WideBoard;
+
+  #[test]
+  fn replacement() {
+    let table = TransTable::new(1);
+    assert_eq!(table.size_bytes(), 1 << 20);
+    let entry = |depth, score| Entry { depth, score, end_prob: 0.25, bound: Bound::Exact, samples: 24 };
+
+    let board = Board(0x1234_0000_0000_0001);
+    assert_eq!(table.get(board), None);
+    table.insert(board, entry(3, 10.0));
+    assert_eq!(table.get(board), Some(entry(3, 10.0)));
+    assert_eq!(table.get(Board(0x1234_0000_0000_0002)), None);
+    // Shallower results don't replace deeper ones of the same board.
+    table.insert(board, entry(2, 20.0));
+    assert_eq!(table.get(board), Some(entry(3, 10.0)));
+    table.insert(board, entry(4, 30.0));
+    assert_eq!(table.get(board), Some(entry(4, 30.0)));
+    // Nor do bounds replace exact scores of the same depth.
+    let lower = Entry { bound: Bound::Lower, ..entry(4, 40.0) };
+    table.insert(board, lower);
+    assert_eq!(table.get(board), Some(entry(4, 30.0)));
+    table.insert(board, Entry { depth: 5, ..lower });
+    assert_eq!(table.get(board), Some(Entry { depth: 5, ..lower }));
+
+    let wide = WideBoard(1 << 100 | 1);
+    table.insert(wide, entry(5, 1.0));
+    assert_eq!(table.get(wide), Some(entry(5, 1.0)));
+    assert_eq!(table.get(WideBoard(1)), None);
+
+    // Fill the bucket of `board`, after which the shallowest result of the
+    // current search is replaced, unless there are results from earlier
+    // searches.
+    let mask = table.mask;
+    let same_bucket: Vec<Board> = (2u64..).map(|n| Board(n << 32))
+      .filter(|b| hash(*b) & mask == hash(board) & mask).take(4).collect();
+    for (n, b) in same_bucket.iter().take(3).enumerate() {
+      table.insert(*b, entry(n as u8 + 1, 0.0));
+    }
+    table.insert(same_bucket[3], entry(9, 0.0));
+    assert_eq!(table.get(same_bucket[0]), None);
+    assert!(table.get(board).is_some() && table.get(same_bucket[3]).is_some());
+
+    table.new_search();
+    table.insert(same_bucket[0], entry(1, 0.0));
+    assert_eq!(table.get(same_bucket[1]), None);
+    assert!(table.get(same_bucket[0]).is_some() && table.get(same_bucket[2]).is_some());
+
+    table.clear();
+    assert_eq!(table.get(board), None);
+  }
+}
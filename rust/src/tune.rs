@@ -0,0 +1,251 @@
+use std;
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use board::Board;
+use config::{self, SearchConfig};
+use game::Game;
+use heuristic::{Heuristic, HeuristicParams};
+use rng::{Rng, XorShift};
+use rules::Ruleset;
+use search::Searcher;
+
+// How candidate weights are scored: each plays `games` games, game n with
+// seed seed+n, so all candidates play the same games.
+#[derive(Debug, Clone)]
+pub struct Evaluation {
+  pub games: u32,
+  pub seed: u32,
+  // Games stop once a tile of this rank is reached, if above 0.
+  pub until: i32,
+  pub rules: Ruleset,
+  pub search_config: SearchConfig,
+  pub hash_mb: usize,
+}
+
+impl Evaluation {
+  fn play(&self, game_num: u32, searcher: &mut Searcher) -> u32 {
+    let mut game = Game::<Board>::new(&self.rules, XorShift::new(self.seed.wrapping_add(game_num)));
+    searcher.new_game();
+    while let Some(dir) = searcher.search(game.board()).dir {
+      if self.until > 0 && game.board().max_val() >= self.until {
+        break;
+      }
+      game.play(dir);
+    }
+    game.score()
+  }
+
+  // The average score of each candidate. The games of all candidates are
+  // shared out between one thread per cpu, each searching with a single
+  // threaded Searcher of its own. A Searcher is kept for as long as its
+  // thread plays games of the same candidate.
+  pub fn run(&self, candidates: &[HeuristicParams]) -> Result<Vec<f64>, String> {
+    debug_assert!(self.games > 0);
+    self.search_config.validate()?;
+    let n_jobs = candidates.len() * self.games as usize;
+    let next_job = AtomicUsize::new(0);
+    let totals = Mutex::new(vec![0u64; candidates.len()]);
+    let n_threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+    thread::scope(|scope| {
+      for _ in 0..n_threads {
+        scope.spawn(|| {
+          let mut current: Option<(usize, Searcher)> = None;
+          loop {
+            let job = next_job.fetch_add(1, Ordering::Relaxed);
+            if job >= n_jobs {
+              break;
+            }
+            let candidate = job / self.games as usize;
+            if current.as_ref().is_none_or(|(n, _)| *n != candidate) {
+              let heuristic = Arc::new(Heuristic::new(candidates[candidate]));
+              let mut searcher = Searcher::with_threads(&self.rules, self.hash_mb, heuristic, 1);
+              // Already checked above.
+              searcher.set_config(self.search_config).unwrap();
+              current = Some((candidate, searcher));
+            }
+            let searcher = &mut current.as_mut().unwrap().1;
+            let score = self.play((job % self.games as usize) as u32, searcher);
+            totals.lock().unwrap()[candidate] += score as u64;
+          }
+        });
+      }
+    });
+
+    Ok(totals.into_inner().unwrap().iter().map(|total| *total as f64 / self.games as f64).collect())
+  }
+}
+
+// Share of each generation whose mean the next generation is drawn around.
+const ELITE_FRACTION: f32 = 0.25;
+// How much of the spread of the elite replaces the previous spread.
+const SMOOTHING: f32 = 0.7;
+// The spread of a weight never falls below this share of its value, so the
+// search doesn't stop moving before it's done.
+const MIN_SPREAD: f32 = 0.01;
+
+// Tunes heuristic weights with the cross-entropy method. Each generation
+// draws candidates from a normal distribution for each weight, and the
+// distribution of the next generation is fitted to the best of them. The
+// mean itself is always the first candidate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tuner {
+  pub seed: u32,
+  pub generation: u32,
+  mean: [f32; 7],
+  spread: [f32; 7],
+  best: Option<(HeuristicParams, f64)>,
+}
+
+fn normal<R: Rng>(rng: &mut R) -> f32 {
+  let uniform = |rng: &mut R| (rng.next_u32() as f64 + 0.5) / 4294967296.0;
+  let (u1, u2) = (uniform(rng), uniform(rng));
+  ((-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()) as f32
+}
+
+impl Tuner {
+  // Starts around `start`, with a spread of a quarter of each weight.
+  pub fn new(start: HeuristicParams, seed: u32) -> Tuner {
+    let mean = start.values().map(|(_, value)| value);
+    let spread = mean.map(|value| if value == 0.0 { 1.0 } else { value.abs() / 4.0 });
+    Tuner { seed, generation: 0, mean, spread, best: None }
+  }
+
+  pub fn mean(&self) -> HeuristicParams {
+    HeuristicParams::from_values(&self.mean)
+  }
+
+  // The best weights so far, with their average score.
+  pub fn best(&self) -> Option<(HeuristicParams, f64)> {
+    self.best
+  }
+
+  // The candidates of the current generation. All weights are kept
+  // positive.
+  pub fn candidates(&self, population: u32) -> Vec<HeuristicParams> {
+    let mut rng = XorShift::new(self.seed ^ self.generation.wrapping_add(1).wrapping_mul(0x9e37_79b9));
+    let mut res = vec![self.mean()];
+    for _ in 1..population {
+      let mut values = [0f32; 7];
+      for (n, value) in values.iter_mut().enumerate() {
+        *value = (self.mean[n] + self.spread[n] * normal(&mut rng)).max(0.0);
+      }
+      res.push(HeuristicParams::from_values(&values));
+    }
+    res
+  }
+
+  // Moves on to the next generation given the scores of the candidates.
+  pub fn update(&mut self, candidates: &[HeuristicParams], scores: &[f64]) {
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by(|a, b| scores[*b].partial_cmp(&scores[*a]).unwrap());
+    if self.best.is_none_or(|(_, score)| scores[order[0]] > score) {
+      self.best = Some((candidates[order[0]], scores[order[0]]));
+    }
+
+    let n_elite = std::cmp::max(2, (candidates.len() as f32 * ELITE_FRACTION).round() as usize);
+    let elite: Vec<[f32; 7]> = order.iter().take(n_elite).map(|n| candidates[*n].values().map(|(_, value)| value)).collect();
+    for n in 0..7 {
+      let mean = elite.iter().map(|values| values[n]).sum::<f32>() / elite.len() as f32;
+      let var = elite.iter().map(|values| (values[n] - mean).powi(2)).sum::<f32>() / elite.len() as f32;
+      self.mean[n] = mean;
+      self.spread[n] = (SMOOTHING * var.sqrt() + (1.0 - SMOOTHING) * self.spread[n]).max(MIN_SPREAD * mean.abs());
+    }
+    self.generation += 1;
+  }
+
+  // The checkpoint is a settings file holding the state of the tuner.
+  pub fn save(&self, path: &str) -> Result<(), String> {
+    let mut text = format!("seed = {}\ngeneration = {}\n", self.seed, self.generation);
+    let names = HeuristicParams::default().values();
+    for (n, (name, _)) in names.iter().enumerate() {
+      text += &format!("mean.{0} = {1}\nspread.{0} = {2}\n", name, self.mean[n], self.spread[n]);
+    }
+    if let Some((params, score)) = self.best {
+      text += &format!("best_score = {}\n", score);
+      for (name, value) in params.values().iter() {
+        text += &format!("best.{} = {}\n", name, value);
+      }
+    }
+    fs::write(path, text).map_err(|e| format!("Can't write {}: {}", path, e))
+  }
+
+  pub fn load(path: &str) -> Result<Tuner, String> {
+    let mut tuner = Tuner::new(HeuristicParams::default(), 0);
+    let names = HeuristicParams::default().values();
+    let mut best = [0f32; 7];
+    let mut best_score = None;
+    for (name, value) in config::read_settings(path)?.iter() {
+      match name.split_once('.') {
+        Some((kind, weight)) => {
+          let n = names.iter().position(|(name, _)| *name == weight)
+            .ok_or_else(|| format!("{}: Unknown heuristic weight: {}", path, weight))?;
+          let value = config::parse_value(name, value)?;
+          match kind {
+            "mean" => tuner.mean[n] = value,
+            "spread" => tuner.spread[n] = value,
+            "best" => best[n] = value,
+            _ => return Err(format!("{}: Unknown checkpoint setting: {}", path, name)),
+          }
+        }
+        None if name == "seed" => tuner.seed = config::parse_value(name, value)?,
+        None if name == "generation" => tuner.generation = config::parse_value(name, value)?,
+        None if name == "best_score" => best_score = Some(config::parse_value(name, value)?),
+        _ => return Err(format!("{}: Unknown checkpoint setting: {}", path, name)),
+      }
+    }
+    tuner.best = best_score.map(|score| (HeuristicParams::from_values(&best), score));
+    Ok(tuner)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn tuner() {
+    let mut tuner = Tuner::new(HeuristicParams::default(), 7);
+    let candidates = tuner.candidates(8);
+    assert_eq!(candidates.len(), 8);
+    assert_eq!(candidates[0], HeuristicParams::default());
+    assert_eq!(tuner.candidates(8), candidates);
+
+    // Reward a high empty weight.
+    let scores: Vec<f64> = candidates.iter().map(|params| params.empty_weight as f64).collect();
+    tuner.update(&candidates, &scores);
+    assert_eq!(tuner.generation, 1);
+    assert!(tuner.mean().empty_weight > HeuristicParams::default().empty_weight);
+    let best = candidates.iter().map(|params| params.empty_weight).fold(0.0, f32::max);
+    assert_eq!(tuner.best().unwrap().0.empty_weight, best);
+    assert!(tuner.candidates(8) != candidates);
+
+    let path = std::env::temp_dir().join(format!("tune-test-{}.checkpoint", std::process::id()));
+    let path = path.to_str().unwrap();
+    tuner.save(path).unwrap();
+    let loaded = Tuner::load(path);
+    fs::remove_file(path).unwrap();
+    assert_eq!(loaded, Ok(tuner));
+  }
+
+  #[test]
+  fn evaluation() {
+    let eval = Evaluation {
+      games: 2,
+      seed: 1,
+      until: 6,
+      rules: Ruleset::default(),
+      search_config: SearchConfig::preset("fast").unwrap(),
+      hash_mb: 1,
+    };
+    let candidates = [HeuristicParams::default(), HeuristicParams { empty_weight: 0.0, ..HeuristicParams::default() }];
+    let scores = eval.run(&candidates).unwrap();
+    assert_eq!(scores.len(), 2);
+    assert!(scores[0] > 0.0);
+    assert_eq!(eval.run(&candidates), Ok(scores));
+    let search_config = SearchConfig { min_depth: 5, max_depth: 4, ..eval.search_config };
+    assert!(Evaluation { search_config, ..eval }.run(&candidates).is_err());
+  }
+}
@@ -0,0 +1,126 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::LazyLock;
+use board::{Board, GameBoard};
+use direction::Direction;
+use notation;
+use rows::RowTables;
+
+// A 4x4 board storing each tile in 5 bits, which allows ranks up to 31
+// rather than stopping at 32768. Slower than Board, so it's meant for
+// analysing games which go beyond what Board can represent.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct WideBoard(pub u128);
+
+impl WideBoard {
+  pub fn from_board(board: Board) -> WideBoard {
+    let mut res = WideBoard(0);
+    for tile in 0..16 {
+      res = res.set_tile(tile, board.get_tile(tile));
+    }
+    res
+  }
+
+  fn slide_rows(self, left: bool) -> (WideBoard, u32) {
+    let (res, reward) = TABLES.slide_rows(self.0, left);
+    (WideBoard(res), reward)
+  }
+}
+
+impl fmt::Display for WideBoard {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    notation::format(*self, f)
+  }
+}
+
+impl FromStr for WideBoard {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<WideBoard, String> {
+    notation::parse(s)
+  }
+}
+
+impl GameBoard for WideBoard {
+  const WIDTH: i32 = 4;
+  const TILE_BITS: u32 = 5;
+  const MAX_RANK: i32 = 31;
+
+  fn from_bits(bits: u128) -> WideBoard {
+    WideBoard(bits)
+  }
+
+  fn bits(self) -> u128 {
+    self.0
+  }
+
+  fn get_tile(self, tile: i32) -> i32 {
+    debug_assert!((0..16).contains(&tile));
+    ((self.0 >> (tile * 5)) & 0x1f) as i32
+  }
+
+  fn set_tile(self, tile: i32, val: i32) -> WideBoard {
+    debug_assert_eq!(self.get_tile(tile), 0);
+    WideBoard(self.0 | (val as u128) << (tile * 5))
+  }
+
+  fn slide(self, dir: Direction) -> WideBoard {
+    self.slide_with_reward(dir).0
+  }
+
+  fn slide_with_reward(self, dir: Direction) -> (WideBoard, u32) {
+    match dir {
+      Direction::Right => self.slide_rows(false),
+      Direction::Down => {
+        let (board, reward) = self.transpose().slide_rows(false);
+        (board.transpose(), reward)
+      }
+      Direction::Left => self.slide_rows(true),
+      Direction::Up => {
+        let (board, reward) = self.transpose().slide_rows(true);
+        (board.transpose(), reward)
+      }
+    }
+  }
+}
+
+static TABLES: LazyLock<RowTables> = LazyLock::new(|| RowTables::new(4, 5, WideBoard::MAX_RANK));
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn matches_board() {
+    let boards = [0x0001_0001_0002_0003u64, 0x0001_0020_0300_1001, 0x5550_0333_aa0a_c0cc,
+                  0x1110_0222_bb0b_e0ee, 0x530c_50ac_53a0_03ac, 0x02be_10be_12b0_120e,
+                  0x1234_5678_9abc_def0, 0x100a_02b0_0c30_d004];
+    for b in boards.iter() {
+      let board = Board(*b);
+      let wide = WideBoard::from_board(board);
+      for dir in Direction::ALL.iter().cloned() {
+        let (slid, reward) = board.slide_with_reward(dir);
+        assert_eq!(wide.slide_with_reward(dir), (WideBoard::from_board(slid), reward));
+      }
+      assert_eq!(wide.transpose(), WideBoard::from_board(board.transpose()));
+      assert_eq!(wide.flip_horiz(), WideBoard::from_board(board.flip_horiz()));
+      assert_eq!(wide.flip_vert(), WideBoard::from_board(board.flip_vert()));
+      assert_eq!(wide.heur_score(), board.heur_score());
+      assert_eq!(wide.empty(), board.empty());
+      assert_eq!(wide.distinct(), board.distinct());
+    }
+  }
+
+  #[test]
+  fn big_tiles() {
+    // Two 32K tiles merge into a 64K tile.
+    let board = WideBoard(0).set_tile(0, 15).set_tile(1, 15).set_tile(4, 17);
+    let (slid, reward) = board.slide_with_reward(Direction::Right);
+    assert_eq!(slid.get_tile(0), 16);
+    assert_eq!(slid.get_tile(4), 17);
+    assert_eq!(slid.empty(), 14);
+    assert_eq!(reward, 65536);
+    assert_eq!(slid.max_val(), 17);
+    assert_eq!(slid.slide(Direction::Up), WideBoard(0).set_tile(12, 17).set_tile(8, 16));
+  }
+}
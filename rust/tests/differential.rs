@@ -0,0 +1,86 @@
+// Checks the table driven Board against the reference grid in
+// p2048::reference, and against the Python board.py through the fixture
+// written by python/dump_fixture.py. Random boards come from a fixed seed, so
+// a failure shows up again on the next run.
+
+extern crate byteorder;
+extern crate p2048;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use p2048::board::{Board, GameBoard};
+use p2048::direction::Direction;
+use p2048::reference::Grid;
+use p2048::rng::{Rng, XorShift};
+
+const SEED: u32 = 2048;
+const RANDOM_BOARDS: u32 = 2_000_000;
+
+fn check(board: Board) {
+  let grid = Grid::from_board(board);
+  assert_eq!(grid.to_board(), board);
+  for dir in Direction::ALL.iter().cloned() {
+    let (slid, reward) = grid.slide(dir);
+    assert_eq!(board.slide_with_reward(dir), (slid.to_board(), reward), "{:#} slid {:?}", board, dir);
+    assert_eq!(board.slide(dir), slid.to_board(), "{:#} slid {:?}", board, dir);
+  }
+  assert_eq!(board.transpose(), grid.transpose().to_board(), "{:#} transposed", board);
+  assert_eq!(board.flip_horiz(), grid.flip_horiz().to_board(), "{:#} flipped", board);
+  assert_eq!(board.flip_vert(), grid.flip_vert().to_board(), "{:#} flipped", board);
+  assert_eq!(board.earned_points(), grid.earned_points(), "{:#} scored", board);
+}
+
+// Every row, in each of the rows and columns of an otherwise empty board.
+#[test]
+fn all_rows() {
+  for row in 0..65536u64 {
+    for n in 0..4 {
+      let board = Board(row << (16 * n));
+      check(board);
+      check(board.transpose());
+    }
+  }
+}
+
+#[test]
+fn random_boards() {
+  let mut rng = XorShift::new(SEED);
+  for _ in 0..RANDOM_BOARDS {
+    // Limiting the ranks makes equal neighbours, and so merges, common.
+    let max_rank = 1 + rng.gen_range(15);
+    let mut board = Board(0);
+    for tile in 0..16 {
+      board.0 |= (rng.gen_range(max_rank + 1) as u64) << (4 * tile);
+    }
+    check(board);
+  }
+}
+
+#[test]
+fn python_fixture() {
+  let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/board_py.bin");
+  let mut file = BufReader::new(File::open(path).unwrap());
+
+  for left in [false, true].iter() {
+    let dir = if *left { Direction::Left } else { Direction::Right };
+    for row in 0..65536u64 {
+      let slid = file.read_u16::<LittleEndian>().unwrap() as u64;
+      assert_eq!(Board(row).slide(dir), Board(slid), "row {:04x} slid {:?}", row, dir);
+    }
+  }
+
+  let n_boards = file.read_u32::<LittleEndian>().unwrap();
+  for _ in 0..n_boards {
+    let mut read = || file.read_u64::<LittleEndian>().unwrap();
+    let board = Board(read());
+    assert_eq!(board.transpose(), Board(read()), "{:#} transposed", board);
+    for dir in Direction::ALL.iter().cloned() {
+      assert_eq!(board.slide(dir), Board(read()), "{:#} slid {:?}", board, dir);
+    }
+    assert_eq!(board.earned_points(), file.read_u32::<LittleEndian>().unwrap() as u64, "{:#} scored", board);
+    assert_eq!(board.empty(), file.read_u32::<LittleEndian>().unwrap() as i32, "{:#} empty tiles", board);
+    check(board);
+  }
+  assert_eq!(file.bytes().count(), 0);
+}